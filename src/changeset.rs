@@ -0,0 +1,499 @@
+//! Serializing detected changes, e.g. to forward them to a queue or another service.
+//!
+//! A [ChangeSet] holds the changes of one calendar (as passed to a [CalendarCallback](crate::CalendarCallback))
+//! and can be written as JSON or CBOR. Both use the same structure:
+//!
+//! ```json
+//! {
+//!   "schema_version": 1,
+//!   "calendar": "Lectures",
+//!   "description": null,
+//!   "changes": [
+//!     {
+//!       "type": "updated",
+//!       "data": {
+//!         "event": {
+//!           "uid": "1",
+//!           "kind": "event",
+//!           "ical_data": {
+//!             "properties": [
+//!               { "name": "UID", "params": null, "value": "1" },
+//!               { "name": "SUMMARY", "params": null, "value": "Algebra" }
+//!             ],
+//!             "alarms": []
+//!           }
+//!         },
+//!         "changed_properties": [
+//!           {
+//!             "key": "SUMMARY",
+//!             "from": { "name": "SUMMARY", "params": null, "value": "Analysis" },
+//!             "to": { "name": "SUMMARY", "params": null, "value": "Algebra" },
+//!             "added": [{ "name": "SUMMARY", "params": null, "value": "Algebra" }],
+//!             "removed": [{ "name": "SUMMARY", "params": null, "value": "Analysis" }]
+//!           }
+//!         ],
+//!         "changed_alarms": []
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! - Every change is tagged with its `type` (the [CalendarEvent] variant in snake case, e.g. `metadata_changed`)
+//!   and holds the fields of the variant in `data`. [AlarmChange](crate::AlarmChange)s are tagged the same way.
+//! - `kind` is the [ComponentKind](crate::ComponentKind) in snake case, e.g. `todo` or `free_busy`.
+//! - Properties are objects with their `name`, raw (escaped) `value` and `params`,
+//!   which is `null` or a list of `[name, [values]]` pairs.
+//! - Components (`ical_data`) hold their `properties` and `alarms`, alarms only their `properties`.
+//! - The VTIMEZONEs referenced by TZID parameters of a component are written next to it as `timezones`
+//!   (left out if there are none), each with its `properties` and `transitions`. A transition holds
+//!   its `kind` (`standard` or `daylight`) and `properties`.
+//!
+//! The format only changes together with [SCHEMA_VERSION]. Change sets of newer versions are rejected
+//! with [Error::UnsupportedSchema].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use ics_watcher::{ChangeSet, ICSWatcher};
+//! let mut ics_watcher = ICSWatcher::new(
+//!     "some url",
+//!     vec![Box::new(|name, description, changes| {
+//!         Box::pin(async move {
+//!             let json = ChangeSet::new(name, description, changes).to_json()?;
+//!             // Publish the JSON
+//!             Ok(())
+//!         })
+//!     })],
+//! );
+//! ```
+
+use ical::{
+    parser::ical::component::{
+        IcalAlarm, IcalEvent, IcalTimeZone, IcalTimeZoneTransition, IcalTimeZoneTransitionType,
+    },
+    property::Property,
+};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{CalendarEvent, ComponentKind, Error, EventData};
+
+/// The version of the format written by this version of the crate
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The changes of one calendar, with the version of their format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeSet {
+    /// See [SCHEMA_VERSION]
+    pub schema_version: u32,
+    /// The name of the calendar (X-WR-CALNAME)
+    pub calendar: Option<String>,
+    /// The description of the calendar (X-WR-CALDESC)
+    pub description: Option<String>,
+    pub changes: Vec<CalendarEvent>,
+}
+
+/// Only the version of a change set, which is read before the rest
+#[derive(Deserialize)]
+struct Version {
+    schema_version: u32,
+}
+
+impl ChangeSet {
+    /// A change set in the current [SCHEMA_VERSION]
+    pub fn new(
+        calendar: Option<String>,
+        description: Option<String>,
+        changes: Vec<CalendarEvent>,
+    ) -> Self {
+        ChangeSet {
+            schema_version: SCHEMA_VERSION,
+            calendar,
+            description,
+            changes,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|error| Error::SerializeChanges(error.into()))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let invalid = |error: serde_json::Error| Error::DeserializeChanges(error.into());
+
+        check_version(serde_json::from_str::<Version>(json).map_err(invalid)?)?;
+        serde_json::from_str(json).map_err(invalid)
+    }
+
+    pub fn to_cbor(&self) -> Result<Vec<u8>, Error> {
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(self, &mut cbor)
+            .map_err(|error| Error::SerializeChanges(error.into()))?;
+        Ok(cbor)
+    }
+
+    pub fn from_cbor(cbor: &[u8]) -> Result<Self, Error> {
+        let invalid = |error: ciborium::de::Error<std::io::Error>| {
+            Error::DeserializeChanges(error.to_string().into())
+        };
+
+        check_version(ciborium::de::from_reader::<Version, _>(cbor).map_err(invalid)?)?;
+        ciborium::de::from_reader(cbor).map_err(invalid)
+    }
+}
+
+fn check_version(version: Version) -> Result<(), Error> {
+    match version.schema_version {
+        version if version > SCHEMA_VERSION => Err(Error::UnsupportedSchema(version)),
+        _ => Ok(()),
+    }
+}
+
+/// Types of the `ical` crate, which are written in a format of this crate instead of their own serde format
+pub(crate) trait Wire: Sized {
+    type Format: Serialize + DeserializeOwned;
+
+    fn to_wire(&self) -> Self::Format;
+    fn from_wire(format: Self::Format) -> Self;
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct WireProperty {
+    name: String,
+    params: Option<Vec<(String, Vec<String>)>>,
+    value: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct WireAlarm {
+    properties: Vec<WireProperty>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct WireComponent {
+    properties: Vec<WireProperty>,
+    alarms: Vec<WireAlarm>,
+}
+
+impl Wire for Property {
+    type Format = WireProperty;
+
+    fn to_wire(&self) -> WireProperty {
+        WireProperty {
+            name: self.name.clone(),
+            params: self.params.clone(),
+            value: self.value.clone(),
+        }
+    }
+
+    fn from_wire(format: WireProperty) -> Self {
+        Property {
+            name: format.name,
+            params: format.params,
+            value: format.value,
+        }
+    }
+}
+
+impl Wire for IcalAlarm {
+    type Format = WireAlarm;
+
+    fn to_wire(&self) -> WireAlarm {
+        WireAlarm {
+            properties: self.properties.to_wire(),
+        }
+    }
+
+    fn from_wire(format: WireAlarm) -> Self {
+        IcalAlarm {
+            properties: Wire::from_wire(format.properties),
+        }
+    }
+}
+
+impl Wire for IcalEvent {
+    type Format = WireComponent;
+
+    fn to_wire(&self) -> WireComponent {
+        WireComponent {
+            properties: self.properties.to_wire(),
+            alarms: self.alarms.to_wire(),
+        }
+    }
+
+    fn from_wire(format: WireComponent) -> Self {
+        IcalEvent {
+            properties: Wire::from_wire(format.properties),
+            alarms: Wire::from_wire(format.alarms),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum WireTransitionKind {
+    Standard,
+    Daylight,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct WireTransition {
+    kind: WireTransitionKind,
+    properties: Vec<WireProperty>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct WireTimezone {
+    properties: Vec<WireProperty>,
+    transitions: Vec<WireTransition>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct WireEventData {
+    uid: String,
+    kind: ComponentKind,
+    ical_data: WireComponent,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    timezones: Vec<WireTimezone>,
+}
+
+impl Wire for IcalTimeZone {
+    type Format = WireTimezone;
+
+    fn to_wire(&self) -> WireTimezone {
+        WireTimezone {
+            properties: self.properties.to_wire(),
+            transitions: self
+                .transitions
+                .iter()
+                .map(|transition| WireTransition {
+                    kind: match transition.transition {
+                        IcalTimeZoneTransitionType::STANDARD => WireTransitionKind::Standard,
+                        IcalTimeZoneTransitionType::DAYLIGHT => WireTransitionKind::Daylight,
+                    },
+                    properties: transition.properties.to_wire(),
+                })
+                .collect(),
+        }
+    }
+
+    fn from_wire(format: WireTimezone) -> Self {
+        IcalTimeZone {
+            properties: Wire::from_wire(format.properties),
+            transitions: format
+                .transitions
+                .into_iter()
+                .map(|transition| IcalTimeZoneTransition {
+                    transition: match transition.kind {
+                        WireTransitionKind::Standard => IcalTimeZoneTransitionType::STANDARD,
+                        WireTransitionKind::Daylight => IcalTimeZoneTransitionType::DAYLIGHT,
+                    },
+                    properties: Wire::from_wire(transition.properties),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Wire for EventData {
+    type Format = WireEventData;
+
+    fn to_wire(&self) -> WireEventData {
+        WireEventData {
+            uid: self.uid.clone(),
+            kind: self.kind,
+            ical_data: self.ical_data.to_wire(),
+            // Only the time zones the event needs, not all of its calendar
+            timezones: self
+                .timezones
+                .used_by(&self.ical_data)
+                .map(Wire::to_wire)
+                .collect(),
+        }
+    }
+
+    fn from_wire(format: WireEventData) -> Self {
+        EventData {
+            uid: format.uid,
+            kind: format.kind,
+            ical_data: Wire::from_wire(format.ical_data),
+            timezones: format.timezones.into_iter().map(Wire::from_wire).collect(),
+        }
+    }
+}
+
+impl Serialize for EventData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        wire::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EventData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        wire::deserialize(deserializer)
+    }
+}
+
+impl<T: Wire> Wire for Option<T> {
+    type Format = Option<T::Format>;
+
+    fn to_wire(&self) -> Self::Format {
+        self.as_ref().map(T::to_wire)
+    }
+
+    fn from_wire(format: Self::Format) -> Self {
+        format.map(T::from_wire)
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    type Format = Vec<T::Format>;
+
+    fn to_wire(&self) -> Self::Format {
+        self.iter().map(T::to_wire).collect()
+    }
+
+    fn from_wire(format: Self::Format) -> Self {
+        format.into_iter().map(T::from_wire).collect()
+    }
+}
+
+/// Used as `#[serde(with = "crate::changeset::wire")]` for fields of a [Wire] type
+pub(crate) mod wire {
+    use super::*;
+
+    pub(crate) fn serialize<T: Wire, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.to_wire().serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, T: Wire, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        T::Format::deserialize(deserializer).map(T::from_wire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{testing, CalendarChangeDetector};
+
+    fn changes() -> Vec<CalendarEvent> {
+        let calendar = |summary: &str| {
+            testing::calendar(&format!(
+                "BEGIN:VEVENT\nUID:1\nSUMMARY:{summary}\nEND:VEVENT\n"
+            ))
+        };
+
+        let mut detector = CalendarChangeDetector::new();
+        detector.compare(calendar("Analysis"));
+        detector.compare(calendar("Algebra"))
+    }
+
+    #[test]
+    fn json_format() {
+        let json = ChangeSet::new(Some(String::from("Lectures")), None, changes())
+            .to_json()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["schema_version"], 1);
+        assert_eq!(value["calendar"], "Lectures");
+        let change = &value["changes"][0];
+        assert_eq!(change["type"], "updated");
+        assert_eq!(change["data"]["event"]["kind"], "event");
+        assert_eq!(change["data"]["changed_properties"][0]["key"], "SUMMARY");
+        assert_eq!(
+            change["data"]["changed_properties"][0]["to"]["value"],
+            "Algebra"
+        );
+
+        let parsed = ChangeSet::from_json(&json).unwrap();
+        assert!(matches!(
+            &parsed.changes[..],
+            [CalendarEvent::Updated { event, .. }] if event.uid == "1"
+        ));
+    }
+
+    #[test]
+    fn json_format_matches_docs() {
+        let docs = include_str!("changeset.rs");
+        let example: String = docs
+            .lines()
+            .skip_while(|line| *line != "//! ```json")
+            .skip(1)
+            .take_while(|line| *line != "//! ```")
+            .map(|line| line.trim_start_matches("//!"))
+            .collect();
+        let example: serde_json::Value = serde_json::from_str(&example).unwrap();
+
+        let json = ChangeSet::new(Some(String::from("Lectures")), None, changes())
+            .to_json()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value, example);
+    }
+
+    #[test]
+    fn referenced_timezones_are_kept() {
+        let timezone = |tzid: &str, offset: &str| {
+            format!(
+                "BEGIN:VTIMEZONE\nTZID:{tzid}\nBEGIN:STANDARD\nDTSTART:19700101T000000\n\
+                 TZOFFSETFROM:{offset}\nTZOFFSETTO:{offset}\nEND:STANDARD\nEND:VTIMEZONE\n"
+            )
+        };
+        let calendar = testing::calendar(&format!(
+            "{}{}BEGIN:VEVENT\nUID:1\nDTSTART;TZID=Campus:20250303T100000\nEND:VEVENT\n",
+            timezone("Campus", "+0300"),
+            timezone("Unused", "+0100")
+        ));
+        let changes = CalendarChangeDetector::new().compare(calendar);
+
+        let json = ChangeSet::new(None, None, changes).to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let timezones = &value["changes"][0]["data"]["timezones"];
+        assert_eq!(timezones.as_array().unwrap().len(), 1);
+        assert_eq!(timezones[0]["transitions"][0]["kind"], "standard");
+
+        let parsed = ChangeSet::from_json(&json).unwrap();
+        let [CalendarEvent::Setup(data)] = &parsed.changes[..] else {
+            panic!("Unexpected changes {:?}", parsed.changes);
+        };
+        assert!(data.timezones.get("Unused").is_none());
+        assert_eq!(
+            data.event().unwrap().start,
+            Some(crate::CalendarTime::DateTime(
+                "2025-03-03T07:00:00Z".parse().unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn cbor_round_trip() {
+        let cbor = ChangeSet::new(None, None, changes()).to_cbor().unwrap();
+        let parsed = ChangeSet::from_cbor(&cbor).unwrap();
+
+        assert_eq!(parsed.schema_version, SCHEMA_VERSION);
+        assert!(matches!(
+            &parsed.changes[..],
+            [CalendarEvent::Updated { .. }]
+        ));
+    }
+
+    #[test]
+    fn newer_schemas_are_rejected() {
+        let json = r#"{"schema_version": 2, "changes": "in a new format"}"#;
+        assert!(matches!(
+            ChangeSet::from_json(json),
+            Err(Error::UnsupportedSchema(2))
+        ));
+        assert!(matches!(
+            ChangeSet::from_json("{}"),
+            Err(Error::DeserializeChanges(_))
+        ));
+    }
+}
@@ -0,0 +1,697 @@
+//! Parsing DATE and DATE-TIME values (DTSTART, DTEND, ...) into points in time.
+//!
+//! Values are interpreted according to their `Z` suffix or `TZID` parameter.
+//! TZIDs are looked up in the IANA time zone database first, then in the VTIMEZONE
+//! components of the calendar the value belongs to (see [Timezones]).
+//!
+//! # Examples
+//!
+//! ```
+//! # use chrono::TimeZone;
+//! # use ical::property::Property;
+//! # use ics_watcher::datetime::{CalendarTime, Timezones};
+//! let dtstart = Property {
+//!     name: "DTSTART".to_string(),
+//!     params: Some(vec![("TZID".to_string(), vec!["Europe/Berlin".to_string()])]),
+//!     value: Some("20250113T100000".to_string()),
+//! };
+//!
+//! assert_eq!(
+//!     CalendarTime::from_property(&dtstart, &Timezones::default()).unwrap(),
+//!     CalendarTime::DateTime(chrono::Utc.with_ymd_and_hms(2025, 1, 13, 9, 0, 0).unwrap())
+//! );
+//! ```
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    sync::Arc,
+};
+
+use chrono::{
+    DateTime, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, Offset, TimeDelta, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use ical::{
+    parser::{
+        ical::component::{IcalCalendar, IcalEvent, IcalTimeZone},
+        Component,
+    },
+    property::Property,
+};
+
+use crate::{recurrence::Rule, Error};
+
+/// The VTIMEZONE components of a calendar by their TZID, which values of this calendar can reference.
+///
+/// Every calendar defines its own time zones, so they are never shared between calendars.
+#[derive(Clone, Default)]
+pub struct Timezones(Arc<HashMap<String, IcalTimeZone>>);
+
+impl Timezones {
+    /// The VTIMEZONE components of `calendar`
+    pub fn of(calendar: &IcalCalendar) -> Self {
+        calendar.timezones.iter().cloned().collect()
+    }
+
+    pub fn get(&self, tzid: &str) -> Option<&IcalTimeZone> {
+        self.0.get(tzid)
+    }
+
+    /// The VTIMEZONEs referenced by a TZID of `component` or its alarms
+    pub(crate) fn used_by<'t>(
+        &'t self,
+        component: &'t IcalEvent,
+    ) -> impl Iterator<Item = &'t IcalTimeZone> {
+        let properties = component
+            .properties
+            .iter()
+            .chain(component.alarms.iter().flat_map(|alarm| &alarm.properties));
+        let tzids: BTreeSet<&str> = properties
+            .filter_map(|property| param(property, "TZID"))
+            .collect();
+        tzids.into_iter().filter_map(|tzid| self.get(tzid))
+    }
+}
+
+impl FromIterator<IcalTimeZone> for Timezones {
+    fn from_iter<T: IntoIterator<Item = IcalTimeZone>>(timezones: T) -> Self {
+        Timezones(Arc::new(
+            timezones
+                .into_iter()
+                .filter_map(|timezone| {
+                    let tzid = timezone.get_property("TZID")?.value.clone()?;
+                    Some((tzid, timezone))
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl fmt::Debug for Timezones {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// How a DATE or DATE-TIME value was written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValueFormat {
+    Date,
+    Local,
+    Utc,
+}
+
+pub(crate) fn parse_value(value: &str) -> Option<(NaiveDateTime, ValueFormat)> {
+    let value = value.trim();
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|at| (at, ValueFormat::Utc));
+    }
+    if value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(|date| (date.into(), ValueFormat::Date));
+    }
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|at| (at, ValueFormat::Local))
+}
+
+pub(crate) fn format_value(at: NaiveDateTime, format: ValueFormat) -> String {
+    match format {
+        ValueFormat::Date => at.format("%Y%m%d").to_string(),
+        ValueFormat::Local => at.format("%Y%m%dT%H%M%S").to_string(),
+        ValueFormat::Utc => at.format("%Y%m%dT%H%M%SZ").to_string(),
+    }
+}
+
+/// The point in time of a DATE or DATE-TIME property
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CalendarTime {
+    /// A whole day (all-day events)
+    Date(NaiveDate),
+    /// A time in UTC or with a time zone, converted to UTC
+    DateTime(DateTime<Utc>),
+    /// A local time without a time zone, which is the same wall-clock time everywhere
+    Floating(NaiveDateTime),
+}
+
+impl CalendarTime {
+    /// Parses the value of a DATE or DATE-TIME property, e.g. DTSTART,
+    /// whose TZID is looked up in `timezones` unless it is an IANA time zone
+    pub fn from_property(property: &Property, timezones: &Timezones) -> Result<Self, Error> {
+        let value = property
+            .value
+            .as_deref()
+            .ok_or_else(|| Error::InvalidDateTime(format!("{} has no value", property.name)))?;
+        let tzid = param(property, "TZID");
+        Self::parse(value, tzid, timezones)
+    }
+
+    /// Parses a DATE or DATE-TIME `value`, which is local to the time zone `tzid` (if any)
+    pub fn parse(value: &str, tzid: Option<&str>, timezones: &Timezones) -> Result<Self, Error> {
+        let (at, format) =
+            parse_value(value).ok_or_else(|| Error::InvalidDateTime(value.to_string()))?;
+        Self::resolve(at, format, tzid, timezones)
+    }
+
+    /// The point in time of `at` as written in `format`, see [CalendarTime::parse]
+    pub(crate) fn resolve(
+        at: NaiveDateTime,
+        format: ValueFormat,
+        tzid: Option<&str>,
+        timezones: &Timezones,
+    ) -> Result<Self, Error> {
+        match (at, format) {
+            (at, ValueFormat::Date) => Ok(CalendarTime::Date(at.date())),
+            (at, ValueFormat::Utc) => Ok(CalendarTime::DateTime(at.and_utc())),
+            (at, ValueFormat::Local) => match tzid {
+                Some(tzid) => resolve_local(at, tzid, timezones)
+                    .map(CalendarTime::DateTime)
+                    .ok_or_else(|| Error::UnknownTimeZone(tzid.to_string())),
+                None => Ok(CalendarTime::Floating(at)),
+            },
+        }
+    }
+
+    pub fn is_date(&self) -> bool {
+        matches!(self, CalendarTime::Date(_))
+    }
+
+    /// The (start of the) value in UTC, dates and floating times are interpreted in `zone`
+    pub fn to_utc(&self, zone: Tz) -> DateTime<Utc> {
+        let local = match self {
+            CalendarTime::DateTime(at) => return *at,
+            CalendarTime::Date(date) => NaiveDateTime::from(*date),
+            CalendarTime::Floating(at) => *at,
+        };
+        in_zone(zone, local).unwrap_or_else(|| local.and_utc())
+    }
+}
+
+/// A DURATION value like `PT1H30M` or `-P1D`.
+///
+/// Days and weeks are nominal (a day keeps the wall-clock time across DST changes),
+/// hours, minutes and seconds are exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CalendarDuration {
+    pub days: i64,
+    pub seconds: i64,
+}
+
+impl CalendarDuration {
+    /// Parses a DURATION value, rejecting anything that isn't valid RFC 5545
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidDuration(value.to_string());
+
+        let trimmed = value.trim();
+        let (sign, rest) = match trimmed.as_bytes().first() {
+            Some(b'-') => (-1, &trimmed[1..]),
+            Some(b'+') => (1, &trimmed[1..]),
+            _ => (1, trimmed),
+        };
+        let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+
+        let (mut days, mut seconds) = (0i64, 0i64);
+        // Designators have to appear in this order, each at most once
+        let mut order = "WDTHMS".chars();
+        let mut number = String::new();
+        let (mut components, mut time_components) = (0, 0);
+        let mut in_time = false;
+
+        for c in rest.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            if !order.any(|designator| designator == c) {
+                return Err(invalid());
+            }
+            if c == 'T' {
+                if !number.is_empty() {
+                    return Err(invalid());
+                }
+                in_time = true;
+                continue;
+            }
+            if number.is_empty() || in_time != matches!(c, 'H' | 'M' | 'S') {
+                return Err(invalid());
+            }
+
+            let amount: i64 = number.parse().map_err(|_| invalid())?;
+            number.clear();
+            components += 1;
+            if in_time {
+                time_components += 1;
+            }
+            let (target, factor) = match c {
+                'W' => (&mut days, 7),
+                'D' => (&mut days, 1),
+                'H' => (&mut seconds, 60 * 60),
+                'M' => (&mut seconds, 60),
+                _ => (&mut seconds, 1),
+            };
+            *target = amount
+                .checked_mul(factor)
+                .and_then(|amount| target.checked_add(amount))
+                .ok_or_else(invalid)?;
+        }
+
+        if components == 0 || !number.is_empty() || (in_time && time_components == 0) {
+            return Err(invalid());
+        }
+
+        let duration = CalendarDuration {
+            days: sign * days,
+            seconds: sign * seconds,
+        };
+        // Durations beyond the range of dates are of no use
+        duration.to_time_delta().ok_or_else(invalid)?;
+        Ok(duration)
+    }
+
+    /// The duration assuming every day has 24 hours, [None] if it exceeds [TimeDelta]
+    pub fn to_time_delta(self) -> Option<TimeDelta> {
+        TimeDelta::try_days(self.days)?.checked_add(&TimeDelta::try_seconds(self.seconds)?)
+    }
+}
+
+/// The start (DTSTART) of an event
+pub fn event_start(event: &impl Component, timezones: &Timezones) -> Result<CalendarTime, Error> {
+    let dtstart = event
+        .get_property("DTSTART")
+        .ok_or_else(|| Error::InvalidDateTime(String::from("DTSTART is missing")))?;
+    CalendarTime::from_property(dtstart, timezones)
+}
+
+/// The end of an event, given as DTEND or derived from DTSTART and DURATION.
+///
+/// [None] if the event has neither.
+pub fn event_end(
+    event: &impl Component,
+    timezones: &Timezones,
+) -> Result<Option<CalendarTime>, Error> {
+    if let Some(dtend) = event.get_property("DTEND") {
+        return CalendarTime::from_property(dtend, timezones).map(Some);
+    }
+    let Some(duration) = event.get_property("DURATION") else {
+        return Ok(None);
+    };
+    let duration = CalendarDuration::parse(duration.value.as_deref().unwrap_or_default())?;
+
+    let dtstart = event
+        .get_property("DTSTART")
+        .ok_or_else(|| Error::InvalidDateTime(String::from("DTSTART is missing")))?;
+    let value = dtstart.value.as_deref().unwrap_or_default();
+    add_duration(value, param(dtstart, "TZID"), duration, timezones).map(Some)
+}
+
+/// The point in time `duration` after the DATE or DATE-TIME `value` (local to `tzid`, if any)
+pub(crate) fn add_duration(
+    value: &str,
+    tzid: Option<&str>,
+    duration: CalendarDuration,
+    timezones: &Timezones,
+) -> Result<CalendarTime, Error> {
+    let (start, format) =
+        parse_value(value).ok_or_else(|| Error::InvalidDateTime(value.to_string()))?;
+    let out_of_range = || Error::InvalidDuration(format!("{value} + {duration:?}"));
+
+    // Nominal days move the wall-clock time, exact seconds are added afterwards
+    let local = TimeDelta::try_days(duration.days)
+        .and_then(|days| start.checked_add_signed(days))
+        .ok_or_else(out_of_range)?;
+    let exact = TimeDelta::try_seconds(duration.seconds).ok_or_else(out_of_range)?;
+
+    let end = match (format, tzid) {
+        (ValueFormat::Date, _) if duration.seconds != 0 => {
+            return Err(Error::InvalidDuration(format!(
+                "All-day events can only last whole days, not {duration:?}"
+            )))
+        }
+        (ValueFormat::Date, _) => CalendarTime::Date(local.date()),
+        (ValueFormat::Utc, _) => CalendarTime::DateTime(
+            local
+                .and_utc()
+                .checked_add_signed(exact)
+                .ok_or_else(out_of_range)?,
+        ),
+        (ValueFormat::Local, Some(tzid)) => CalendarTime::DateTime(
+            resolve_local(local, tzid, timezones)
+                .ok_or_else(|| Error::UnknownTimeZone(tzid.to_string()))?
+                .checked_add_signed(exact)
+                .ok_or_else(out_of_range)?,
+        ),
+        (ValueFormat::Local, None) => {
+            CalendarTime::Floating(local.checked_add_signed(exact).ok_or_else(out_of_range)?)
+        }
+    };
+    Ok(end)
+}
+
+pub(crate) fn param<'p>(property: &'p Property, name: &str) -> Option<&'p str> {
+    property
+        .params
+        .as_ref()?
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))?
+        .1
+        .first()
+        .map(String::as_str)
+}
+
+/// The first of ambiguous times (when the clocks are turned back), [None] for skipped times
+fn earliest<T: TimeZone>(result: LocalResult<DateTime<T>>) -> Option<DateTime<T>> {
+    match result {
+        LocalResult::Single(at) | LocalResult::Ambiguous(at, _) => Some(at),
+        LocalResult::None => None,
+    }
+}
+
+fn resolve_local(local: NaiveDateTime, tzid: &str, timezones: &Timezones) -> Option<DateTime<Utc>> {
+    let tzid = tzid.trim_matches('"');
+
+    if let Ok(zone) = tzid.parse::<Tz>() {
+        return in_zone(zone, local);
+    }
+
+    if let Some(offset) = timezones
+        .get(tzid)
+        .and_then(|timezone| vtimezone_offset(timezone, local))
+    {
+        return Some((local - offset.fix()).and_utc());
+    }
+
+    // Some clients prefix the IANA name, e.g. /mozilla.org/20050126_1/Europe/Berlin
+    let mut segments = tzid.rsplit('/');
+    let (last, second_last) = (segments.next()?, segments.next()?);
+    format!("{second_last}/{last}")
+        .parse::<Tz>()
+        .ok()
+        .and_then(|zone| in_zone(zone, local))
+}
+
+/// The wall-clock time of `instant` in the time zone `tzid`, the inverse of [resolve_local]
+pub(crate) fn local_time(
+    instant: DateTime<Utc>,
+    tzid: &str,
+    timezones: &Timezones,
+) -> Option<NaiveDateTime> {
+    let utc = instant.naive_utc();
+    // The offset at the UTC time is only a guess close to DST changes, the second one is right
+    let mut local = utc;
+    for _ in 0..2 {
+        let offset = local - resolve_local(local, tzid, timezones)?.naive_utc();
+        local = utc + offset;
+    }
+    Some(local)
+}
+
+fn in_zone(zone: Tz, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    match earliest(zone.from_local_datetime(&local)) {
+        Some(at) => Some(at.with_timezone(&Utc)),
+        // Skipped by a DST gap, use the offset before the gap (like RFC 5545 asks for),
+        // which is still in effect a day earlier
+        None => {
+            let before = zone
+                .offset_from_utc_datetime(&(local - TimeDelta::days(1)))
+                .fix();
+            Some((local - before).and_utc())
+        }
+    }
+}
+
+/// Parses UTC offsets like `+0100`, `-0530` or `+013000`
+fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim();
+    let (sign, digits) = match value.split_at_checked(1)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    if !matches!(digits.len(), 4 | 6) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[0..2].parse().ok()?;
+    let minutes: i32 = digits[2..4].parse().ok()?;
+    let seconds: i32 = digits.get(4..6).map_or(Some(0), |s| s.parse().ok())?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// The UTC offset of `local` according to the observances (STANDARD/DAYLIGHT) of a VTIMEZONE
+fn vtimezone_offset(timezone: &IcalTimeZone, local: NaiveDateTime) -> Option<FixedOffset> {
+    let mut latest: Option<(NaiveDateTime, FixedOffset)> = None;
+    let mut first: Option<(NaiveDateTime, FixedOffset)> = None;
+
+    for observance in &timezone.transitions {
+        let value = |name| {
+            observance
+                .get_property(name)
+                .and_then(|prop| prop.value.as_deref())
+        };
+        let Some((start, _)) = value("DTSTART").and_then(parse_value) else {
+            continue;
+        };
+        let Some(offset_to) = value("TZOFFSETTO").and_then(parse_offset) else {
+            continue;
+        };
+        let offset_from = value("TZOFFSETFROM")
+            .and_then(parse_offset)
+            .unwrap_or(offset_to);
+
+        if first.is_none_or(|(at, _)| start < at) {
+            first = Some((start, offset_from));
+        }
+
+        let mut onsets: Vec<NaiveDateTime> = match value("RRULE").and_then(Rule::parse) {
+            // Onsets are written in the offset before them
+            Some(rule) => rule.occurrences(start, local, |at| (at - offset_from).and_utc()),
+            None => vec![start],
+        };
+        onsets.extend(
+            observance
+                .properties
+                .iter()
+                .filter(|prop| prop.name == "RDATE")
+                .filter_map(|prop| prop.value.as_deref())
+                .flat_map(|value| value.split(','))
+                .filter_map(parse_value)
+                .map(|(at, _)| at),
+        );
+
+        if let Some(onset) = onsets.into_iter().filter(|at| *at <= local).max() {
+            if latest.is_none_or(|(at, _)| onset > at) {
+                latest = Some((onset, offset_to));
+            }
+        }
+    }
+
+    latest.or(first).map(|(_, offset)| offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::testing;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> CalendarTime {
+        CalendarTime::DateTime(Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap())
+    }
+
+    #[test]
+    fn parses_dates_and_utc_times() {
+        assert_eq!(
+            CalendarTime::parse("20250113", None, &Timezones::default()).unwrap(),
+            CalendarTime::Date(NaiveDate::from_ymd_opt(2025, 1, 13).unwrap())
+        );
+        assert_eq!(
+            CalendarTime::parse(
+                "20250113T100000Z",
+                Some("Europe/Berlin"),
+                &Timezones::default()
+            )
+            .unwrap(),
+            utc(2025, 1, 13, 10, 0)
+        );
+        assert!(CalendarTime::parse("2025011", None, &Timezones::default()).is_err());
+    }
+
+    #[test]
+    fn honours_iana_time_zones() {
+        // Winter (UTC+1) and summer (UTC+2) time
+        assert_eq!(
+            CalendarTime::parse(
+                "20250113T100000",
+                Some("Europe/Berlin"),
+                &Timezones::default()
+            )
+            .unwrap(),
+            utc(2025, 1, 13, 9, 0)
+        );
+        assert_eq!(
+            CalendarTime::parse(
+                "20250714T100000",
+                Some("/mozilla.org/20050126_1/Europe/Berlin"),
+                &Timezones::default()
+            )
+            .unwrap(),
+            utc(2025, 7, 14, 8, 0)
+        );
+        assert!(matches!(
+            CalendarTime::parse("20250714T100000", Some("Nowhere"), &Timezones::default()),
+            Err(Error::UnknownTimeZone(_))
+        ));
+    }
+
+    #[test]
+    fn honours_embedded_vtimezones() {
+        let calendar = |standard: &str, daylight: &str| {
+            testing::calendar(&format!(
+                "BEGIN:VTIMEZONE\nTZID:W. Europe Standard Time\n\
+                 BEGIN:STANDARD\nDTSTART:16011028T030000\nRRULE:FREQ=YEARLY;BYDAY=-1SU;BYMONTH=10\nTZOFFSETFROM:{daylight}\nTZOFFSETTO:{standard}\nEND:STANDARD\n\
+                 BEGIN:DAYLIGHT\nDTSTART:16010325T020000\nRRULE:FREQ=YEARLY;BYDAY=-1SU;BYMONTH=3\nTZOFFSETFROM:{standard}\nTZOFFSETTO:{daylight}\nEND:DAYLIGHT\n\
+                 END:VTIMEZONE\n"
+            ))
+        };
+        let timezones = Timezones::of(&calendar("+0100", "+0200"));
+
+        let tzid = Some("W. Europe Standard Time");
+        assert_eq!(
+            CalendarTime::parse("20250113T100000", tzid, &timezones).unwrap(),
+            utc(2025, 1, 13, 9, 0)
+        );
+        assert_eq!(
+            CalendarTime::parse("20250714T100000", tzid, &timezones).unwrap(),
+            utc(2025, 7, 14, 8, 0)
+        );
+
+        // Another calendar defining the same TZID differently doesn't interfere
+        let other = Timezones::of(&calendar("+0000", "+0100"));
+        assert_eq!(
+            CalendarTime::parse("20250113T100000", tzid, &other).unwrap(),
+            utc(2025, 1, 13, 10, 0)
+        );
+        assert_eq!(
+            CalendarTime::parse("20250113T100000", tzid, &timezones).unwrap(),
+            utc(2025, 1, 13, 9, 0)
+        );
+        assert!(CalendarTime::parse("20250113T100000", tzid, &Timezones::default()).is_err());
+    }
+
+    #[test]
+    fn times_in_dst_gap_use_offset_before_gap() {
+        // Berlin skips from 02:00 to 03:00 on the 30th of March 2025
+        assert_eq!(
+            CalendarTime::parse(
+                "20250330T023000",
+                Some("Europe/Berlin"),
+                &Timezones::default()
+            )
+            .unwrap(),
+            utc(2025, 3, 30, 1, 30)
+        );
+    }
+
+    #[test]
+    fn floating_times_and_dates_use_given_zone() {
+        let floating = CalendarTime::parse("20250113T100000", None, &Timezones::default()).unwrap();
+        assert_eq!(
+            floating.to_utc(chrono_tz::Europe::Berlin),
+            Utc.with_ymd_and_hms(2025, 1, 13, 9, 0, 0).unwrap()
+        );
+        let date = CalendarTime::parse("20250714", None, &Timezones::default()).unwrap();
+        assert_eq!(
+            date.to_utc(chrono_tz::Europe::Berlin),
+            Utc.with_ymd_and_hms(2025, 7, 13, 22, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_durations() {
+        let duration = |value| CalendarDuration::parse(value).ok();
+
+        assert_eq!(
+            duration("P1W"),
+            Some(CalendarDuration {
+                days: 7,
+                seconds: 0
+            })
+        );
+        assert_eq!(
+            duration("P1DT2H30M"),
+            Some(CalendarDuration {
+                days: 1,
+                seconds: 9000
+            })
+        );
+        assert_eq!(
+            duration("-PT15M"),
+            Some(CalendarDuration {
+                days: 0,
+                seconds: -900
+            })
+        );
+        for invalid in [
+            "", "P", "PT", "1H", "P1H", "PT1D", "P1DT", "PT1M1H", "P1D2D", "PT1.5H",
+        ] {
+            assert_eq!(duration(invalid), None, "{invalid} should be invalid");
+        }
+        // Out of range of TimeDelta
+        for overflowing in [
+            "P200000000000000D",
+            "PT9999999999999999S",
+            "-P200000000000000D",
+        ] {
+            assert!(matches!(
+                CalendarDuration::parse(overflowing),
+                Err(Error::InvalidDuration(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn end_from_duration() {
+        let event = |properties: &str| testing::event(&format!("UID:1\n{properties}"));
+
+        // A nominal day keeps the wall-clock time across the change to summer time
+        let dst = event("DTSTART;TZID=Europe/Berlin:20250329T100000\nDURATION:P1DT1H\n");
+        assert_eq!(
+            event_end(&dst, &Timezones::default()).unwrap(),
+            Some(utc(2025, 3, 30, 9, 0))
+        );
+
+        let all_day = event("DTSTART;VALUE=DATE:20250303\nDURATION:P2D\n");
+        assert_eq!(
+            event_end(&all_day, &Timezones::default()).unwrap(),
+            Some(CalendarTime::Date(
+                NaiveDate::from_ymd_opt(2025, 3, 5).unwrap()
+            ))
+        );
+        assert!(event_end(
+            &event("DTSTART;VALUE=DATE:20250303\nDURATION:PT1H\n"),
+            &Timezones::default()
+        )
+        .is_err());
+        assert_eq!(
+            event_end(&event("DTSTART:20250303T100000Z\n"), &Timezones::default()).unwrap(),
+            None
+        );
+
+        // Valid durations can still end beyond the range of dates
+        for properties in [
+            "DTSTART:20250303T100000Z\nDURATION:P100000000D\n",
+            "DTSTART:20250303T100000Z\nDURATION:PT9000000000000S\n",
+            "DTSTART;TZID=Europe/Berlin:20250303T100000\nDURATION:PT9000000000000S\n",
+            "DTSTART:20250303T100000\nDURATION:-PT9000000000000S\n",
+        ] {
+            assert!(matches!(
+                event_end(&event(properties), &Timezones::default()),
+                Err(Error::InvalidDuration(_))
+            ));
+        }
+    }
+}
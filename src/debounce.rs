@@ -0,0 +1,184 @@
+//! Debouncing changes of feeds which alternate between two versions of an event.
+//!
+//! A debounced [`CalendarEvent::Updated`] or [`CalendarEvent::Deleted`] is only reported once the
+//! new state of the event was stable long enough (see [Debounce]). Until then it is pending, and
+//! changes are detected against the last reported state. Pending changes are part of the backup.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use ical::parser::ical::component::IcalEvent;
+use serde::{Deserialize, Serialize};
+
+use crate::{datetime::Timezones, diff, CalendarEvent, IgnoredProperties};
+
+/// When a change is stable enough to be reported, see [DetectorConfig::debounce](crate::DetectorConfig::debounce)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Debounce {
+    /// Once it was seen on this many consecutive polls
+    Polls(u32),
+    /// Once it persisted for this long
+    Duration(Duration),
+}
+
+impl Debounce {
+    fn is_stable(&self, change: &PendingChange, now: SystemTime) -> bool {
+        match self {
+            Debounce::Polls(polls) => change.polls >= *polls,
+            Debounce::Duration(duration) => {
+                now.duration_since(change.since).unwrap_or_default() >= *duration
+            }
+        }
+    }
+}
+
+/// A change which wasn't stable long enough to be reported yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingChange {
+    /// The new state of the event, [None] if it was deleted
+    pub state: Option<IcalEvent>,
+    /// Consecutive polls the change was seen on
+    pub polls: u32,
+    /// When the change was seen first
+    pub since: SystemTime,
+}
+
+impl PendingChange {
+    fn is_same(
+        &self,
+        state: Option<&IcalEvent>,
+        ignored: &IgnoredProperties,
+        timezones: &Timezones,
+    ) -> bool {
+        match (&self.state, state) {
+            (None, None) => true,
+            (Some(pending), Some(state)) => {
+                diff::event_changes(pending, state, ignored, timezones).is_empty()
+                    && diff::alarm_changes(pending, state, ignored, timezones).is_empty()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Holds back the updates and deletions in `changes` which aren't stable yet.
+///
+/// Their events keep their `previous` state in `current`, so they are compared against
+/// the last reported state on the next poll. Changes which weren't seen again are dropped from `pending`.
+pub(crate) fn settle(
+    debounce: Debounce,
+    pending: &mut HashMap<String, PendingChange>,
+    changes: Vec<CalendarEvent>,
+    previous: &HashMap<String, IcalEvent>,
+    current: &mut HashMap<String, IcalEvent>,
+    ignored: &IgnoredProperties,
+    now: SystemTime,
+) -> Vec<CalendarEvent> {
+    let mut still_pending = HashMap::new();
+    let mut result = Vec::with_capacity(changes.len());
+
+    for change in changes {
+        let (uid, state, timezones) = match &change {
+            CalendarEvent::Updated { event, .. } => {
+                (event.uid.clone(), Some(&event.ical_data), &event.timezones)
+            }
+            CalendarEvent::Deleted(data) => (data.uid.clone(), None, &data.timezones),
+            _ => {
+                result.push(change);
+                continue;
+            }
+        };
+
+        let pending_change = match pending.remove(&uid) {
+            Some(seen) if seen.is_same(state, ignored, timezones) => PendingChange {
+                polls: seen.polls + 1,
+                ..seen
+            },
+            _ => PendingChange {
+                state: state.cloned(),
+                polls: 1,
+                since: now,
+            },
+        };
+        if debounce.is_stable(&pending_change, now) {
+            result.push(change);
+            continue;
+        }
+
+        if let Some(state) = previous.get(&uid) {
+            current.insert(uid.clone(), state.clone());
+        }
+        still_pending.insert(uid, pending_change);
+    }
+
+    *pending = still_pending;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, CalendarChangeDetector, DetectorConfig};
+
+    fn calendar(description: Option<&str>) -> ical::parser::ical::component::IcalCalendar {
+        let event = description
+            .map(|description| {
+                format!("BEGIN:VEVENT\nUID:1\nSUMMARY:Analysis\nDESCRIPTION:{description}\nEND:VEVENT\n")
+            })
+            .unwrap_or_default();
+        testing::calendar(&format!("{event}BEGIN:VEVENT\nUID:2\nEND:VEVENT\n"))
+    }
+
+    #[test]
+    fn flapping_changes_are_not_reported() {
+        let mut detector = CalendarChangeDetector::with_config(DetectorConfig {
+            debounce: Some(Debounce::Polls(2)),
+            ..Default::default()
+        });
+        detector.compare(calendar(Some("Lecture")));
+
+        assert!(detector.compare(calendar(Some("Vorlesung"))).is_empty());
+        assert_eq!(detector.get_pending().len(), 1);
+        assert!(detector.compare(calendar(Some("Lecture"))).is_empty());
+        assert!(detector.get_pending().is_empty());
+
+        assert!(detector.compare(calendar(Some("Vorlesung"))).is_empty());
+        match &detector.compare(calendar(Some("Vorlesung")))[..] {
+            [CalendarEvent::Updated {
+                changed_properties, ..
+            }] => {
+                assert_eq!(changed_properties[0].key, "DESCRIPTION");
+                assert_eq!(
+                    changed_properties[0]
+                        .from
+                        .as_ref()
+                        .and_then(|prop| prop.value.as_deref()),
+                    Some("Lecture")
+                );
+            }
+            changes => panic!("Unexpected changes {changes:?}"),
+        }
+
+        assert!(detector.compare(calendar(None)).is_empty());
+        assert_eq!(detector.get_state().len(), 2);
+        assert!(matches!(
+            &detector.compare(calendar(None))[..],
+            [CalendarEvent::Deleted(_)]
+        ));
+    }
+
+    #[test]
+    fn durations() {
+        let change = PendingChange {
+            state: None,
+            polls: 1,
+            since: SystemTime::UNIX_EPOCH,
+        };
+        let later = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+
+        assert!(Debounce::Duration(Duration::from_secs(60)).is_stable(&change, later));
+        assert!(!Debounce::Duration(Duration::from_secs(61)).is_stable(&change, later));
+    }
+}
@@ -0,0 +1,1324 @@
+//! Comparing two states of an event, property by property.
+//!
+//! Values are normalised before they are compared, so writing the same value differently
+//! (a time in UTC instead of with a TZID, reordered parameters or categories, escaped text)
+//! isn't reported as a change.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+
+use ical::{
+    parser::{
+        ical::component::{IcalAlarm, IcalEvent},
+        Component,
+    },
+    property::Property,
+};
+use regex::Regex;
+
+use crate::{
+    datetime::{self, add_duration, param, CalendarDuration, CalendarTime, Timezones},
+    AlarmChange, Error, PropertyChange,
+};
+
+/// Decides by the name of a property whether it is ignored
+pub type PropertyPredicate = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// The properties which are ignored when comparing two states of an event,
+/// see [DetectorConfig::ignored_properties](crate::DetectorConfig::ignored_properties).
+///
+/// By default only DTSTAMP (the time the feed was generated) is ignored.
+///
+/// # Examples
+///
+/// ```
+/// # use ics_watcher::IgnoredProperties;
+/// # use regex::Regex;
+/// let ignored = IgnoredProperties::default()
+///     .ignore("LAST-MODIFIED")
+///     .ignore("SEQUENCE")
+///     .ignore_matching(Regex::new("^X-MICROSOFT-").unwrap())
+///     .ignore_if(|name| name.ends_with("-TRACKING"));
+///
+/// assert!(ignored.is_ignored("X-MICROSOFT-CDO-BUSYSTATUS"));
+/// assert!(!ignored.is_ignored("SUMMARY"));
+/// ```
+#[derive(Clone)]
+pub struct IgnoredProperties {
+    names: HashSet<String>,
+    patterns: Vec<Regex>,
+    predicate: Option<PropertyPredicate>,
+}
+
+impl Default for IgnoredProperties {
+    fn default() -> Self {
+        IgnoredProperties::none().ignore("DTSTAMP")
+    }
+}
+
+impl fmt::Debug for IgnoredProperties {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IgnoredProperties")
+            .field("names", &self.names)
+            .field("patterns", &self.patterns)
+            .field(
+                "predicate",
+                &self.predicate.as_ref().map(|_| "Fn(&str) -> bool"),
+            )
+            .finish()
+    }
+}
+
+impl IgnoredProperties {
+    /// Compares all properties, including DTSTAMP
+    pub fn none() -> Self {
+        IgnoredProperties {
+            names: HashSet::new(),
+            patterns: Vec::new(),
+            predicate: None,
+        }
+    }
+
+    /// Ignores the property `name` (case-insensitive)
+    pub fn ignore(mut self, name: impl Into<String>) -> Self {
+        self.names.insert(name.into().to_uppercase());
+        self
+    }
+
+    /// Ignores all properties whose name matches `pattern`
+    pub fn ignore_matching(mut self, pattern: Regex) -> Self {
+        self.patterns.push(pattern);
+        self
+    }
+
+    /// Ignores all properties for whose name `predicate` returns true, replacing any previous predicate
+    pub fn ignore_if(mut self, predicate: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        self.predicate = Some(Arc::new(predicate));
+        self
+    }
+
+    pub fn is_ignored(&self, name: &str) -> bool {
+        self.names.contains(&name.to_uppercase())
+            || self.patterns.iter().any(|pattern| pattern.is_match(name))
+            || self
+                .predicate
+                .as_ref()
+                .is_some_and(|predicate| predicate(name))
+    }
+}
+
+/// Properties containing a single DATE or DATE-TIME
+const DATE_TIME_PROPERTIES: &[&str] = &[
+    "DTSTART",
+    "DTEND",
+    "DUE",
+    "RECURRENCE-ID",
+    "CREATED",
+    "LAST-MODIFIED",
+    "COMPLETED",
+];
+
+/// Properties containing a list of DATE or DATE-TIME values
+const DATE_TIME_LIST_PROPERTIES: &[&str] = &["EXDATE", "RDATE"];
+
+/// Properties containing an unordered list of texts
+const TEXT_LIST_PROPERTIES: &[&str] = &["CATEGORIES", "RESOURCES"];
+
+/// Properties containing a DURATION (TRIGGER can also contain a DATE-TIME)
+const DURATION_PROPERTIES: &[&str] = &["DURATION", "TRIGGER", "X-PUBLISHED-TTL"];
+
+/// Properties containing one of a fixed set of case-insensitive values
+const ENUMERATED_PROPERTIES: &[&str] = &["ACTION", "STATUS", "TRANSP", "CLASS"];
+
+/// Properties containing (escaped) text
+const TEXT_PROPERTIES: &[&str] = &["SUMMARY", "DESCRIPTION", "LOCATION", "COMMENT", "CONTACT"];
+
+/// Properties which have to match for a deleted and a created event to be reported as moved
+const MOVE_PROPERTIES: &[&str] = &["SUMMARY", "DTSTART", "LOCATION"];
+
+/// The value of a property, independent of how it was written
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Value {
+    Duration(CalendarDuration),
+    /// Points in time, along with their end if they are periods
+    Times(BTreeSet<(CalendarTime, Option<CalendarTime>)>),
+    Texts(BTreeSet<String>),
+    Text(String),
+}
+
+/// Resolves the escape sequences of a TEXT value (`\n`, `\,`, `\;` and `\\`)
+pub(crate) fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(escaped) => unescaped.push(escaped),
+                None => unescaped.push('\\'),
+            },
+            '\r' => {}
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Splits a list of TEXT values at the commas which aren't escaped, without unescaping them
+fn split_escaped_list(value: &str) -> impl Iterator<Item = &str> {
+    let mut escaped = false;
+    value.split(move |c| {
+        let split = c == ',' && !escaped;
+        escaped = c == '\\' && !escaped;
+        split
+    })
+}
+
+pub(crate) fn split_text_list(value: &str) -> impl Iterator<Item = String> + '_ {
+    split_escaped_list(value)
+        .map(|text| unescape_text(text).trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// Splits a property holding a list of values into one property per value
+fn single_values(property: &Property) -> Vec<Property> {
+    let name = property.name.as_str();
+    let values: Vec<&str> = match property.value.as_deref() {
+        Some(value) if TEXT_LIST_PROPERTIES.contains(&name) => split_escaped_list(value)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .collect(),
+        Some(value) if DATE_TIME_LIST_PROPERTIES.contains(&name) => value.split(',').collect(),
+        _ => return vec![property.clone()],
+    };
+
+    values
+        .into_iter()
+        .map(|value| Property {
+            name: property.name.clone(),
+            params: property.params.clone(),
+            value: Some(value.to_string()),
+        })
+        .collect()
+}
+
+/// The values of the property `name`, one property per value
+fn values_of(properties: &[Property], name: &str) -> Vec<Property> {
+    properties
+        .iter()
+        .filter(|property| property.name == name)
+        .flat_map(single_values)
+        .collect()
+}
+
+/// The values only in `before` (removed) and only in `after` (added), compared as multisets
+fn multiset_difference(
+    before: Vec<Property>,
+    mut after: Vec<Property>,
+    timezones: &Timezones,
+) -> (Vec<Property>, Vec<Property>) {
+    let normalised = |property: &Property| (property.name.clone(), normalise(property, timezones));
+
+    // The indices of the unmatched values in `after` by their normalised form, the first one last
+    let mut unmatched: HashMap<_, Vec<usize>> = HashMap::new();
+    for (index, property) in after.iter().enumerate().rev() {
+        unmatched
+            .entry(normalised(property))
+            .or_default()
+            .push(index);
+    }
+
+    let mut matched = vec![false; after.len()];
+    let mut removed = Vec::new();
+    for property in before {
+        match unmatched.get_mut(&normalised(&property)).and_then(Vec::pop) {
+            Some(index) => matched[index] = true,
+            None => removed.push(property),
+        }
+    }
+
+    let mut matched = matched.into_iter();
+    after.retain(|_| !matched.next().unwrap_or_default());
+    (removed, after)
+}
+
+/// How the property `key` changed between two states of a component
+pub(crate) fn property_change(
+    key: &str,
+    previous: &[Property],
+    current: &[Property],
+    timezones: &Timezones,
+) -> PropertyChange {
+    let (removed, added) =
+        multiset_difference(values_of(previous, key), values_of(current, key), timezones);
+    PropertyChange {
+        key: key.to_string(),
+        from: previous
+            .iter()
+            .find(|property| property.name == key)
+            .cloned(),
+        to: current
+            .iter()
+            .find(|property| property.name == key)
+            .cloned(),
+        added,
+        removed,
+    }
+}
+
+/// How the properties changed between two states of a component
+pub(crate) fn property_changes(
+    previous: &[Property],
+    current: &[Property],
+    ignored: &IgnoredProperties,
+    timezones: &Timezones,
+) -> Vec<PropertyChange> {
+    changed_names(previous, current, ignored, timezones)
+        .iter()
+        .map(|key| property_change(key, previous, current, timezones))
+        .collect()
+}
+
+/// The names of the properties which differ between two states of a component
+fn changed_names(
+    properties1: &[Property],
+    properties2: &[Property],
+    ignored: &IgnoredProperties,
+    timezones: &Timezones,
+) -> Vec<String> {
+    let mut names: Vec<&str> = Vec::new();
+    for property in properties1.iter().chain(properties2) {
+        if !ignored.is_ignored(&property.name) && !names.contains(&property.name.as_str()) {
+            names.push(&property.name);
+        }
+    }
+
+    // Properties can occur multiple times (e.g. ATTENDEE), all their values are compared
+    names
+        .into_iter()
+        .filter(|name| {
+            let (removed, added) = multiset_difference(
+                values_of(properties1, name),
+                values_of(properties2, name),
+                timezones,
+            );
+            !removed.is_empty() || !added.is_empty()
+        })
+        .map(String::from)
+        .collect()
+}
+
+/// Moves every alarm of `removed` together with the first alarm of `added` it `matches` into `pairs`
+fn pair_alarms<'a>(
+    removed: &mut Vec<&'a IcalAlarm>,
+    added: &mut Vec<&'a IcalAlarm>,
+    pairs: &mut Vec<(&'a IcalAlarm, &'a IcalAlarm)>,
+    matches: impl Fn(&IcalAlarm, &IcalAlarm) -> bool,
+) {
+    removed.retain(
+        |before| match added.iter().position(|after| matches(before, after)) {
+            Some(index) => {
+                pairs.push((*before, added.remove(index)));
+                false
+            }
+            None => true,
+        },
+    );
+}
+
+/// How the reminders (VALARM) changed between two states of an event.
+///
+/// Alarms are matched by their UID if they have one. Otherwise unchanged alarms are matched first,
+/// the remaining ones are paired in order and reported as [AlarmChange::Updated].
+pub(crate) fn alarm_changes(
+    previous: &IcalEvent,
+    current: &IcalEvent,
+    ignored: &IgnoredProperties,
+    timezones: &Timezones,
+) -> Vec<AlarmChange> {
+    let uid = |alarm: &IcalAlarm| alarm.get_property("UID").and_then(|uid| uid.value.clone());
+    let unchanged = |a: &IcalAlarm, b: &IcalAlarm| {
+        changed_names(&a.properties, &b.properties, ignored, timezones).is_empty()
+    };
+
+    let mut removed: Vec<&IcalAlarm> = previous.alarms.iter().collect();
+    let mut added: Vec<&IcalAlarm> = current.alarms.iter().collect();
+    let mut pairs = Vec::new();
+
+    pair_alarms(&mut removed, &mut added, &mut pairs, |a, b| {
+        uid(a).is_some() && uid(a) == uid(b)
+    });
+    pair_alarms(&mut removed, &mut added, &mut pairs, unchanged);
+    // Without UIDs, the remaining alarms are assumed to be the same alarms in a different state
+    pair_alarms(&mut removed, &mut added, &mut pairs, |a, b| {
+        uid(a).is_none() && uid(b).is_none()
+    });
+
+    let mut changes: Vec<AlarmChange> = pairs
+        .into_iter()
+        .filter_map(|(before, after)| {
+            let changed = changed_names(&before.properties, &after.properties, ignored, timezones);
+            (!changed.is_empty()).then(|| AlarmChange::Updated {
+                changed_properties: changed
+                    .iter()
+                    .map(|key| {
+                        property_change(key, &before.properties, &after.properties, timezones)
+                    })
+                    .collect(),
+                from: before.clone(),
+                to: after.clone(),
+            })
+        })
+        .collect();
+    changes.extend(removed.into_iter().cloned().map(AlarmChange::Removed));
+    changes.extend(added.into_iter().cloned().map(AlarmChange::Added));
+    changes
+}
+
+/// Normalises the value of `property`, along with the parameters relevant for it
+fn normalise(
+    property: &Property,
+    timezones: &Timezones,
+) -> (Option<Value>, BTreeSet<(String, Vec<String>)>) {
+    let name = property.name.as_str();
+    let mut ignored_params: &[&str] = &[];
+
+    let value = property.value.as_deref().map(|value| {
+        if DURATION_PROPERTIES.contains(&name) {
+            if let Ok(duration) = CalendarDuration::parse(value) {
+                ignored_params = &["VALUE"];
+                return Value::Duration(duration);
+            }
+        }
+        if DATE_TIME_PROPERTIES.contains(&name)
+            || DATE_TIME_LIST_PROPERTIES.contains(&name)
+            || name == "TRIGGER"
+        {
+            let tzid = param(property, "TZID");
+            let times = value
+                .split(',')
+                .map(|value| normalise_period(value, tzid, timezones))
+                .collect::<Result<BTreeSet<_>, _>>();
+            if let Ok(times) = times {
+                // The time zone and type are part of the parsed times
+                ignored_params = &["TZID", "VALUE"];
+                return Value::Times(times);
+            }
+        }
+        if TEXT_LIST_PROPERTIES.contains(&name) {
+            return Value::Texts(split_text_list(value).collect());
+        }
+        if TEXT_PROPERTIES.contains(&name) {
+            return Value::Text(unescape_text(value));
+        }
+        if ENUMERATED_PROPERTIES.contains(&name) {
+            return Value::Text(value.trim().to_uppercase());
+        }
+        Value::Text(value.to_string())
+    });
+
+    let params = property
+        .params
+        .iter()
+        .flatten()
+        .map(|(key, values)| {
+            let mut values = values.clone();
+            values.sort();
+            (key.to_uppercase(), values)
+        })
+        .filter(|(key, _)| !ignored_params.contains(&key.as_str()))
+        .collect();
+
+    (value, params)
+}
+
+/// A DATE or DATE-TIME, or the start and end of a PERIOD (which can be given by its duration)
+fn normalise_period(
+    value: &str,
+    tzid: Option<&str>,
+    timezones: &Timezones,
+) -> Result<(CalendarTime, Option<CalendarTime>), Error> {
+    let Some((start, end)) = value.split_once('/') else {
+        return Ok((CalendarTime::parse(value, tzid, timezones)?, None));
+    };
+    let end = match CalendarDuration::parse(end) {
+        Ok(duration) => add_duration(start, tzid, duration, timezones)?,
+        Err(_) => CalendarTime::parse(end, tzid, timezones)?,
+    };
+    Ok((CalendarTime::parse(start, tzid, timezones)?, Some(end)))
+}
+
+/// The names of the properties which differ between two states of an event, [None] if nothing changed
+pub(crate) fn changed_properties(
+    event1: &IcalEvent,
+    event2: &IcalEvent,
+    ignored: &IgnoredProperties,
+    timezones: &Timezones,
+) -> Option<Vec<String>> {
+    let changed_props = changed_names(&event1.properties, &event2.properties, ignored, timezones);
+
+    without_equivalent_end(event1, event2, changed_props, timezones)
+}
+
+/// How updates to an older version of an event are handled, see [DetectorConfig::stale_versions](crate::DetectorConfig::stale_versions).
+///
+/// Outdated (e.g. cached) copies of a feed would otherwise revert changes, which are then applied again
+/// once the feed catches up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StalenessPolicy {
+    /// Report them like any other update
+    #[default]
+    Report,
+    /// Don't report them
+    Suppress,
+    /// Report them as [CalendarEvent::Regressed](crate::CalendarEvent::Regressed)
+    Flag,
+}
+
+/// Whether `current` is an older version of `previous`, according to their SEQUENCE or (if it is the same) LAST-MODIFIED
+pub(crate) fn is_regression(
+    previous: &IcalEvent,
+    current: &IcalEvent,
+    timezones: &Timezones,
+) -> bool {
+    let sequence = |event: &IcalEvent| {
+        event
+            .get_property("SEQUENCE")
+            .and_then(|prop| prop.value.as_deref())
+            .and_then(|value| value.trim().parse::<i64>().ok())
+    };
+    if let (Some(before), Some(after)) = (sequence(previous), sequence(current)) {
+        if before != after {
+            return after < before;
+        }
+    }
+
+    let last_modified = |event: &IcalEvent| {
+        event
+            .get_property("LAST-MODIFIED")
+            .and_then(|prop| CalendarTime::from_property(prop, timezones).ok())
+    };
+    matches!(
+        (last_modified(previous), last_modified(current)),
+        (Some(before), Some(after)) if after < before
+    )
+}
+
+/// How the properties of an event changed, see [changed_properties]
+pub(crate) fn event_changes(
+    previous: &IcalEvent,
+    current: &IcalEvent,
+    ignored: &IgnoredProperties,
+    timezones: &Timezones,
+) -> Vec<PropertyChange> {
+    changed_properties(previous, current, ignored, timezones)
+        .unwrap_or_default()
+        .iter()
+        .map(|key| property_change(key, &previous.properties, &current.properties, timezones))
+        .collect()
+}
+
+/// Whether two events with different UIDs are the same event, i.e. have the same
+/// [MOVE_PROPERTIES] (after all, some publishers regenerate their UIDs)
+pub(crate) fn is_moved(previous: &IcalEvent, current: &IcalEvent, timezones: &Timezones) -> bool {
+    let identifying = |event: &IcalEvent| -> Vec<Property> {
+        event
+            .properties
+            .iter()
+            .filter(|property| MOVE_PROPERTIES.contains(&property.name.as_str()))
+            .cloned()
+            .collect()
+    };
+    let previous = identifying(previous);
+
+    !previous.is_empty()
+        && changed_names(
+            &previous,
+            &identifying(current),
+            &IgnoredProperties::none(),
+            timezones,
+        )
+        .is_empty()
+}
+
+/// Drops DTEND and DURATION from the `properties` changed between two states of an event
+/// if the event still ends at the same time, e.g. when switching from DTEND to DURATION
+fn without_equivalent_end(
+    previous: &IcalEvent,
+    current: &IcalEvent,
+    mut properties: Vec<String>,
+    timezones: &Timezones,
+) -> Option<Vec<String>> {
+    let is_end = |property: &String| property == "DTEND" || property == "DURATION";
+
+    if properties.iter().any(is_end) {
+        if let (Ok(Some(before)), Ok(Some(after))) = (
+            datetime::event_end(previous, timezones),
+            datetime::event_end(current, timezones),
+        ) {
+            if before == after {
+                properties.retain(|property| !is_end(property));
+            }
+        }
+    }
+
+    (!properties.is_empty()).then_some(properties)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether two properties have the same meaning, independent of how they are written
+    fn same_property(property1: &Property, property2: &Property, timezones: &Timezones) -> bool {
+        property1.name == property2.name
+            && normalise(property1, timezones) == normalise(property2, timezones)
+    }
+
+    #[test]
+    fn cmp_no_properties() {
+        let event1 = IcalEvent {
+            properties: vec![],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        );
+
+        assert_eq!(keys, None);
+    }
+
+    #[test]
+    fn cmp_same_properties() {
+        let prop1 = Property {
+            name: String::from("prop1"),
+            value: Some(String::from("prop1 value")),
+            params: None,
+        };
+
+        let prop2 = Property {
+            name: String::from("prop2"),
+            value: Some(String::from("prop2 value")),
+            params: None,
+        };
+
+        let event1 = IcalEvent {
+            properties: vec![prop1.clone(), prop2.clone()],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![prop2.clone(), prop1.clone()],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        );
+
+        assert_eq!(keys, None);
+    }
+
+    #[test]
+    fn cmp_different_properties() {
+        let prop1 = Property {
+            name: String::from("prop1"),
+            value: Some(String::from("prop1 value")),
+            params: None,
+        };
+
+        let prop2 = Property {
+            name: String::from("prop2"),
+            value: Some(String::from("prop2 value")),
+            params: None,
+        };
+
+        let event1 = IcalEvent {
+            properties: vec![prop1],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![prop2],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        )
+        .expect("Keys should be Some");
+
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&String::from("prop1")) && keys.contains(&String::from("prop2")));
+    }
+
+    #[test]
+    fn cmp_added_property() {
+        let prop1 = Property {
+            name: String::from("prop1"),
+            value: Some(String::from("prop1 value")),
+            params: None,
+        };
+
+        let event1 = IcalEvent {
+            properties: vec![],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![prop1],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        )
+        .expect("Keys should be Some");
+
+        assert_eq!(keys.len(), 1);
+        assert!(keys.contains(&String::from("prop1")));
+    }
+
+    #[test]
+    fn cmp_removed_property() {
+        let prop1 = Property {
+            name: String::from("prop1"),
+            value: Some(String::from("prop1 value")),
+            params: None,
+        };
+
+        let event1 = IcalEvent {
+            properties: vec![prop1],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        )
+        .expect("Keys should be Some");
+
+        assert_eq!(keys.len(), 1);
+        assert!(keys.contains(&String::from("prop1")));
+    }
+
+    #[test]
+    fn cmp_different_properties_no_value() {
+        let prop1 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: None,
+        };
+
+        let prop2 = Property {
+            name: String::from("prop2"),
+            value: None,
+            params: None,
+        };
+
+        let event1 = IcalEvent {
+            properties: vec![prop1],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![prop2],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        )
+        .expect("Keys should be Some");
+
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&String::from("prop1")) && keys.contains(&String::from("prop2")));
+    }
+
+    #[test]
+    fn cmp_different_params() {
+        let prop1 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
+        };
+
+        let prop2 = Property {
+            name: String::from("prop2"),
+            value: None,
+            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
+        };
+
+        let event1 = IcalEvent {
+            properties: vec![prop1],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![prop2],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        )
+        .expect("Keys should be Some");
+
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&String::from("prop1")) && keys.contains(&String::from("prop2")));
+    }
+
+    #[test]
+    fn cmp_same_params() {
+        let prop1 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
+        };
+
+        let prop2 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
+        };
+
+        let event1 = IcalEvent {
+            properties: vec![prop1],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![prop2],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        );
+
+        assert_eq!(keys, None);
+    }
+
+    #[test]
+    fn cmp_different_param_keys() {
+        let prop1 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
+        };
+
+        let prop2 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: Some(vec![(String::from("key2"), vec![String::from("value")])]),
+        };
+
+        let event1 = IcalEvent {
+            properties: vec![prop1],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![prop2],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        )
+        .expect("Keys should be Some");
+
+        assert_eq!(keys.len(), 1);
+        assert!(keys.contains(&String::from("prop1")));
+    }
+
+    #[test]
+    fn cmp_different_param_values() {
+        let prop1 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
+        };
+
+        let prop2 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: Some(vec![(String::from("key"), vec![String::from("value2")])]),
+        };
+
+        let event1 = IcalEvent {
+            properties: vec![prop1],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![prop2],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        )
+        .expect("Keys should be Some");
+
+        assert_eq!(keys.len(), 1);
+        assert!(keys.contains(&String::from("prop1")));
+    }
+
+    #[test]
+    fn cmp_added_param() {
+        let prop1 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: None,
+        };
+
+        let prop2 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: Some(vec![(String::from("key"), vec![String::from("value2")])]),
+        };
+
+        let event1 = IcalEvent {
+            properties: vec![prop1],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![prop2],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        )
+        .expect("Keys should be Some");
+
+        assert_eq!(keys.len(), 1);
+        assert!(keys.contains(&String::from("prop1")));
+    }
+
+    #[test]
+    fn cmp_removed_param() {
+        let prop1 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: Some(vec![(String::from("key"), vec![String::from("value2")])]),
+        };
+
+        let prop2 = Property {
+            name: String::from("prop1"),
+            value: None,
+            params: None,
+        };
+
+        let event1 = IcalEvent {
+            properties: vec![prop1],
+            alarms: vec![],
+        };
+
+        let event2 = IcalEvent {
+            properties: vec![prop2],
+            alarms: vec![],
+        };
+
+        let keys = changed_properties(
+            &event1,
+            &event2,
+            &IgnoredProperties::default(),
+            &Timezones::default(),
+        )
+        .expect("Keys should be Some");
+
+        assert_eq!(keys.len(), 1);
+        assert!(keys.contains(&String::from("prop1")));
+    }
+
+    #[test]
+    fn cmp_ignored_properties() {
+        let event = |sequence: &str, summary: &str| IcalEvent {
+            properties: vec![
+                property("SEQUENCE", &[], sequence),
+                property("X-MICROSOFT-CDO-APPT-SEQUENCE", &[], sequence),
+                property("SUMMARY", &[], summary),
+            ],
+            alarms: vec![],
+        };
+        let ignored = IgnoredProperties::default()
+            .ignore("sequence")
+            .ignore_matching(Regex::new("^X-MICROSOFT-").unwrap());
+
+        assert_eq!(
+            changed_properties(
+                &event("1", "Exam"),
+                &event("2", "Exam"),
+                &ignored,
+                &Timezones::default()
+            ),
+            None
+        );
+        assert_eq!(
+            changed_properties(
+                &event("1", "Exam"),
+                &event("2", "Retake"),
+                &ignored,
+                &Timezones::default()
+            ),
+            Some(vec![String::from("SUMMARY")])
+        );
+        assert_eq!(
+            changed_properties(
+                &event("1", "Exam"),
+                &event("1", "Retake"),
+                &IgnoredProperties::none().ignore_if(|name| name == "SUMMARY"),
+                &Timezones::default()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn cmp_repeated_properties() {
+        let event = |properties: Vec<Property>| IcalEvent {
+            properties,
+            alarms: vec![],
+        };
+        let jane = property("ATTENDEE", &[("CN", "Jane")], "mailto:jane@example.com");
+        let john = property("ATTENDEE", &[("CN", "John")], "mailto:john@example.com");
+        let max = property("ATTENDEE", &[("CN", "Max")], "mailto:max@example.com");
+
+        let before = event(vec![jane.clone(), john.clone()]);
+        let after = event(vec![john.clone(), jane.clone()]);
+        assert_eq!(
+            changed_properties(
+                &before,
+                &after,
+                &IgnoredProperties::default(),
+                &Timezones::default()
+            ),
+            None
+        );
+
+        // The first ATTENDEE stays the same, but the second one is replaced
+        let after = event(vec![jane.clone(), max.clone()]);
+        assert_eq!(
+            changed_properties(
+                &before,
+                &after,
+                &IgnoredProperties::default(),
+                &Timezones::default()
+            ),
+            Some(vec![String::from("ATTENDEE")])
+        );
+        let change = property_change(
+            "ATTENDEE",
+            &before.properties,
+            &after.properties,
+            &Timezones::default(),
+        );
+        assert_eq!(change.removed, vec![john]);
+        assert_eq!(change.added, vec![max]);
+    }
+
+    #[test]
+    fn cmp_regressions() {
+        let event = |sequence: Option<&str>, last_modified: &str| IcalEvent {
+            properties: sequence
+                .map(|sequence| property("SEQUENCE", &[], sequence))
+                .into_iter()
+                .chain([property("LAST-MODIFIED", &[], last_modified)])
+                .collect(),
+            alarms: vec![],
+        };
+
+        let current = event(Some("2"), "20250303T090000Z");
+        assert!(is_regression(
+            &current,
+            &event(Some("1"), "20250304T090000Z"),
+            &Timezones::default()
+        ));
+        assert!(!is_regression(
+            &current,
+            &event(Some("3"), "20250302T090000Z"),
+            &Timezones::default()
+        ));
+        // LAST-MODIFIED only decides if SEQUENCE is the same or missing
+        assert!(is_regression(
+            &current,
+            &event(Some("2"), "20250302T090000Z"),
+            &Timezones::default()
+        ));
+        assert!(is_regression(
+            &current,
+            &event(None, "20250302T090000Z"),
+            &Timezones::default()
+        ));
+        assert!(!is_regression(
+            &current,
+            &event(None, "20250303T090000Z"),
+            &Timezones::default()
+        ));
+    }
+
+    #[test]
+    fn cmp_list_values() {
+        let event = |categories: &str, exdates: &[&str]| IcalEvent {
+            properties: std::iter::once(property("CATEGORIES", &[], categories))
+                .chain(exdates.iter().map(|exdate| property("EXDATE", &[], exdate)))
+                .collect(),
+            alarms: vec![],
+        };
+
+        let before = event("Lecture,Exam", &["20250303T090000Z", "20250310T090000Z"]);
+        let after = event(
+            "Exam,Tutorial",
+            &["20250310T090000Z,20250303T090000Z", "20250317T090000Z"],
+        );
+
+        let categories = property_change(
+            "CATEGORIES",
+            &before.properties,
+            &after.properties,
+            &Timezones::default(),
+        );
+        assert_eq!(
+            categories.removed,
+            vec![property("CATEGORIES", &[], "Lecture")]
+        );
+        assert_eq!(
+            categories.added,
+            vec![property("CATEGORIES", &[], "Tutorial")]
+        );
+
+        let exdates = property_change(
+            "EXDATE",
+            &before.properties,
+            &after.properties,
+            &Timezones::default(),
+        );
+        assert!(exdates.removed.is_empty());
+        assert_eq!(
+            exdates.added,
+            vec![property("EXDATE", &[], "20250317T090000Z")]
+        );
+    }
+
+    #[test]
+    fn cmp_alarms() {
+        let alarm = |trigger: &str, action: &str| IcalAlarm {
+            properties: vec![
+                property("ACTION", &[], action),
+                property("TRIGGER", &[], trigger),
+            ],
+        };
+        let event = |alarms: Vec<IcalAlarm>| IcalEvent {
+            properties: vec![property("SUMMARY", &[], "Exam")],
+            alarms,
+        };
+        let ignored = IgnoredProperties::default();
+
+        // Equivalent triggers and actions written differently aren't a change
+        let before = event(vec![alarm("-PT15M", "DISPLAY"), alarm("-P1D", "EMAIL")]);
+        let same = event(vec![alarm("-P1D", "EMAIL"), alarm("-PT0H15M", "display")]);
+        assert!(alarm_changes(&before, &same, &ignored, &Timezones::default()).is_empty());
+
+        let after = event(vec![alarm("-PT30M", "DISPLAY")]);
+        match &alarm_changes(&before, &after, &ignored, &Timezones::default())[..] {
+            [AlarmChange::Updated {
+                changed_properties, ..
+            }, AlarmChange::Removed(removed)] => {
+                assert_eq!(changed_properties.len(), 1);
+                assert_eq!(changed_properties[0].key, "TRIGGER");
+                assert_eq!(removed.properties[0].value.as_deref(), Some("EMAIL"));
+            }
+            changes => panic!("Unexpected changes {changes:?}"),
+        }
+
+        let after = event(vec![
+            alarm("-PT15M", "DISPLAY"),
+            alarm("-P1D", "EMAIL"),
+            alarm("-PT5M", "AUDIO"),
+        ]);
+        assert!(matches!(
+            &alarm_changes(&before, &after, &ignored, &Timezones::default())[..],
+            [AlarmChange::Added(_)]
+        ));
+    }
+
+    fn property(name: &str, params: &[(&str, &str)], value: &str) -> Property {
+        Property {
+            name: String::from(name),
+            params: (!params.is_empty()).then(|| {
+                params
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), vec![value.to_string()]))
+                    .collect()
+            }),
+            value: Some(String::from(value)),
+        }
+    }
+
+    #[test]
+    fn multiset_difference_counts_duplicates() {
+        let attendee = |value| property("ATTENDEE", &[], value);
+        let (removed, added) = multiset_difference(
+            vec![attendee("mailto:a@tum.de"), attendee("mailto:a@tum.de")],
+            vec![
+                attendee("mailto:b@tum.de"),
+                attendee("mailto:a@tum.de"),
+                attendee("mailto:c@tum.de"),
+            ],
+            &Timezones::default(),
+        );
+        assert_eq!(removed, vec![attendee("mailto:a@tum.de")]);
+        assert_eq!(
+            added,
+            vec![attendee("mailto:b@tum.de"), attendee("mailto:c@tum.de")]
+        );
+    }
+
+    #[test]
+    fn cmp_times_as_instants() {
+        assert!(same_property(
+            &property("DTSTART", &[("TZID", "Europe/Berlin")], "20250303T100000"),
+            &property("DTSTART", &[], "20250303T090000Z"),
+            &Timezones::default()
+        ));
+        assert!(!same_property(
+            &property("DTSTART", &[("TZID", "Europe/Berlin")], "20250303T100000"),
+            &property("DTSTART", &[], "20250303T100000Z"),
+            &Timezones::default()
+        ));
+        assert!(same_property(
+            &property("EXDATE", &[], "20250303T090000Z,20250310T090000Z"),
+            &property("EXDATE", &[], "20250310T090000Z,20250303T090000Z"),
+            &Timezones::default()
+        ));
+    }
+
+    #[test]
+    fn cmp_periods_by_start_and_end() {
+        assert!(same_property(
+            &property("RDATE", &[("VALUE", "PERIOD")], "20250303T090000Z/PT2H"),
+            &property(
+                "RDATE",
+                &[("VALUE", "PERIOD")],
+                "20250303T090000Z/20250303T110000Z"
+            ),
+            &Timezones::default()
+        ));
+        assert!(!same_property(
+            &property("RDATE", &[("VALUE", "PERIOD")], "20250303T090000Z/PT2H"),
+            &property("RDATE", &[("VALUE", "PERIOD")], "20250303T090000Z/PT3H"),
+            &Timezones::default()
+        ));
+    }
+
+    #[test]
+    fn cmp_published_ttl_as_duration() {
+        assert!(same_property(
+            &property("X-PUBLISHED-TTL", &[], "PT1H"),
+            &property("X-PUBLISHED-TTL", &[], "PT60M"),
+            &Timezones::default()
+        ));
+    }
+
+    #[test]
+    fn cmp_categories_as_sets() {
+        assert!(same_property(
+            &property("CATEGORIES", &[], "Lecture,Exam"),
+            &property("CATEGORIES", &[], "Exam, Lecture"),
+            &Timezones::default()
+        ));
+        assert!(!same_property(
+            &property("CATEGORIES", &[], "Lecture\\,Exam"),
+            &property("CATEGORIES", &[], "Lecture,Exam"),
+            &Timezones::default()
+        ));
+    }
+
+    #[test]
+    fn cmp_unescaped_text() {
+        assert!(same_property(
+            &property("SUMMARY", &[], "Analysis\\; Algebra"),
+            &property("SUMMARY", &[], "Analysis; Algebra"),
+            &Timezones::default()
+        ));
+        assert!(same_property(
+            &property("DESCRIPTION", &[], "First\\nSecond"),
+            &property("DESCRIPTION", &[], "First\\NSecond"),
+            &Timezones::default()
+        ));
+        assert!(!same_property(
+            &property("SUMMARY", &[], "Analysis"),
+            &property("SUMMARY", &[], "analysis"),
+            &Timezones::default()
+        ));
+    }
+
+    #[test]
+    fn cmp_params_order_insensitive() {
+        let mut reordered = property(
+            "ATTENDEE",
+            &[("CN", "Jane"), ("ROLE", "CHAIR")],
+            "mailto:jane@example.com",
+        );
+        reordered.params.as_mut().unwrap().reverse();
+
+        assert!(same_property(
+            &property(
+                "ATTENDEE",
+                &[("CN", "Jane"), ("ROLE", "CHAIR")],
+                "mailto:jane@example.com"
+            ),
+            &reordered,
+            &Timezones::default()
+        ));
+    }
+}
@@ -0,0 +1,155 @@
+//! The error type of this crate.
+
+use std::{fmt, io};
+
+use ical::parser::ParserError;
+use reqwest::StatusCode;
+
+use crate::retry::is_transient_status;
+
+/// Everything that can go wrong while watching a calendar
+#[derive(Debug)]
+pub enum Error {
+    /// The calendar couldn't be downloaded (e.g. the server is unreachable or timed out).
+    ///
+    /// The URL is removed from the error, as it often contains access tokens.
+    Fetch(reqwest::Error),
+    /// The server answered with an error status
+    HttpStatus(StatusCode),
+    /// A local calendar couldn't be read or watched
+    Io(io::Error),
+    /// The ics file isn't valid iCalendar
+    Parse(ParserError),
+    /// The ics file doesn't contain a single calendar
+    NoCalendar,
+    /// The backup couldn't be read or written
+    BackupIo(io::Error),
+    /// The backup couldn't be serialized
+    BackupEncode(ciborium::ser::Error<io::Error>),
+    /// The backup is corrupted or of an unknown format
+    BackupDecode(ciborium::de::Error<io::Error>),
+    /// A DATE or DATE-TIME value couldn't be parsed
+    InvalidDateTime(String),
+    /// A DURATION value isn't valid
+    InvalidDuration(String),
+    /// A TZID is neither a known time zone nor defined by a VTIMEZONE of the calendar
+    UnknownTimeZone(String),
+    /// A [ChangeSet](crate::ChangeSet) couldn't be serialized
+    SerializeChanges(Box<dyn std::error::Error + Send + Sync>),
+    /// A serialized [ChangeSet](crate::ChangeSet) is invalid
+    DeserializeChanges(Box<dyn std::error::Error + Send + Sync>),
+    /// A [ChangeSet](crate::ChangeSet) was written with a newer schema version than this one supports
+    UnsupportedSchema(u32),
+    /// A callback failed
+    Callback(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    /// Wraps any error of a callback, e.g. `Err(Error::callback("Event has no start"))`
+    pub fn callback(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Error::Callback(error.into())
+    }
+
+    /// Whether the error might go away by itself, which makes retrying worthwhile.
+    ///
+    /// Network errors, timeouts, [transient HTTP statuses](is_transient_status) and I/O errors
+    /// (e.g. a file being replaced while reading it) are transient.
+    /// Other HTTP statuses and calendars which can't be parsed are permanent.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Fetch(error) => !error.is_builder() && !error.is_redirect(),
+            Error::HttpStatus(status) => is_transient_status(*status),
+            Error::Io(_) => true,
+            Error::Parse(_)
+            | Error::NoCalendar
+            | Error::BackupIo(_)
+            | Error::BackupEncode(_)
+            | Error::BackupDecode(_)
+            | Error::InvalidDateTime(_)
+            | Error::InvalidDuration(_)
+            | Error::UnknownTimeZone(_)
+            | Error::SerializeChanges(_)
+            | Error::DeserializeChanges(_)
+            | Error::UnsupportedSchema(_)
+            | Error::Callback(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Fetch(error) => write!(f, "Fetching the calendar failed: {error}"),
+            Error::HttpStatus(status) => write!(f, "Server responded with {status}"),
+            Error::Io(error) => write!(f, "Reading the calendar failed: {error}"),
+            Error::Parse(error) => write!(f, "Parsing the calendar failed: {error}"),
+            Error::NoCalendar => write!(f, "No Calendar present"),
+            Error::BackupIo(error) => write!(f, "Accessing the backup failed: {error}"),
+            Error::BackupEncode(error) => write!(f, "Writing the backup failed: {error}"),
+            Error::BackupDecode(error) => write!(f, "Reading the backup failed: {error}"),
+            Error::InvalidDateTime(value) => write!(f, "Invalid date or time: {value}"),
+            Error::InvalidDuration(value) => write!(f, "Invalid duration: {value}"),
+            Error::UnknownTimeZone(tzid) => write!(f, "Unknown time zone {tzid}"),
+            Error::SerializeChanges(error) => write!(f, "Serializing the changes failed: {error}"),
+            Error::DeserializeChanges(error) => write!(f, "Invalid changes: {error}"),
+            Error::UnsupportedSchema(version) => {
+                write!(f, "Unsupported schema version {version} of the changes")
+            }
+            Error::Callback(error) => write!(f, "Error in callback: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Fetch(error) => Some(error),
+            Error::Io(error) | Error::BackupIo(error) => Some(error),
+            Error::Parse(error) => Some(error),
+            Error::BackupEncode(error) => Some(error),
+            Error::BackupDecode(error) => Some(error),
+            Error::Callback(error)
+            | Error::SerializeChanges(error)
+            | Error::DeserializeChanges(error) => Some(error.as_ref()),
+            Error::HttpStatus(_)
+            | Error::NoCalendar
+            | Error::InvalidDateTime(_)
+            | Error::InvalidDuration(_)
+            | Error::UnknownTimeZone(_)
+            | Error::UnsupportedSchema(_) => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(error: reqwest::Error) -> Self {
+        match error.status() {
+            Some(status) => Error::HttpStatus(status),
+            None => Error::Fetch(error.without_url()),
+        }
+    }
+}
+
+impl From<ParserError> for Error {
+    fn from(error: ParserError) -> Self {
+        Error::Parse(error)
+    }
+}
+
+impl From<ciborium::ser::Error<io::Error>> for Error {
+    fn from(error: ciborium::ser::Error<io::Error>) -> Self {
+        Error::BackupEncode(error)
+    }
+}
+
+impl From<ciborium::de::Error<io::Error>> for Error {
+    fn from(error: ciborium::de::Error<io::Error>) -> Self {
+        Error::BackupDecode(error)
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for Error {
+    fn from(error: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        Error::Callback(error)
+    }
+}
@@ -0,0 +1,309 @@
+//! A typed view of the properties of an event.
+//!
+//! [Event] saves callbacks from reading (and unescaping) the raw properties of an [IcalEvent]
+//! themselves.
+//!
+//! # Examples
+//!
+//! ```
+//! # use ics_watcher::{event::EventStatus, Event};
+//! let ics = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:1\nSUMMARY:Analysis 1\\, Exercise\n\
+//!            DTSTART;TZID=Europe/Berlin:20250303T101500\nSTATUS:CONFIRMED\nEND:VEVENT\nEND:VCALENDAR\n";
+//! let calendar = ical::IcalParser::new(ics.as_bytes()).next().unwrap().unwrap();
+//!
+//! let event = Event::try_from(&calendar.events[0]).unwrap();
+//! assert_eq!(event.summary.as_deref(), Some("Analysis 1, Exercise"));
+//! assert_eq!(event.status, Some(EventStatus::Confirmed));
+//! assert_eq!(
+//!     event.start_in(chrono_tz::Europe::Berlin).unwrap().to_rfc3339(),
+//!     "2025-03-03T10:15:00+01:00"
+//! );
+//! ```
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use ical::{
+    parser::{ical::component::IcalEvent, Component},
+    property::Property,
+};
+
+use crate::{
+    datetime::{self, param, CalendarTime, Timezones},
+    diff::{split_text_list, unescape_text},
+    Error,
+};
+
+/// The properties of an event (or another component), with their values parsed and unescaped
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Event {
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub start: Option<CalendarTime>,
+    /// Given as DTEND or derived from DTSTART and DURATION
+    pub end: Option<CalendarTime>,
+    pub status: Option<EventStatus>,
+    pub url: Option<String>,
+    pub categories: Vec<String>,
+    pub organizer: Option<Participant>,
+    pub attendees: Vec<Participant>,
+    pub recurrence: Recurrence,
+}
+
+/// The STATUS of an event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventStatus {
+    Tentative,
+    Confirmed,
+    Cancelled,
+    /// A status of tasks or journals (e.g. `COMPLETED`) or an extension
+    Other(String),
+}
+
+impl EventStatus {
+    /// The status as written in iCalendar, e.g. `CONFIRMED`
+    pub fn name(&self) -> &str {
+        match self {
+            EventStatus::Tentative => "TENTATIVE",
+            EventStatus::Confirmed => "CONFIRMED",
+            EventStatus::Cancelled => "CANCELLED",
+            EventStatus::Other(status) => status,
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "TENTATIVE" => EventStatus::Tentative,
+            "CONFIRMED" => EventStatus::Confirmed,
+            "CANCELLED" => EventStatus::Cancelled,
+            other => EventStatus::Other(other.to_string()),
+        }
+    }
+}
+
+/// The ORGANIZER or an ATTENDEE of an event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Participant {
+    /// The address without `mailto:`, usually an email address
+    pub address: String,
+    /// The common name (CN)
+    pub name: Option<String>,
+    /// The ROLE, e.g. `REQ-PARTICIPANT`
+    pub role: Option<String>,
+    /// The participation status (PARTSTAT), e.g. `ACCEPTED`
+    pub status: Option<String>,
+}
+
+impl Participant {
+    fn from_property(property: &Property) -> Option<Self> {
+        let value = property.value.as_deref()?.trim();
+        let address = match value.get(..7) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &value[7..],
+            _ => value,
+        };
+        let param = |name| param(property, name).map(|value| value.trim_matches('"').to_string());
+
+        Some(Participant {
+            address: address.to_string(),
+            name: param("CN"),
+            role: param("ROLE"),
+            status: param("PARTSTAT"),
+        })
+    }
+}
+
+/// How an event recurs
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Recurrence {
+    /// The RRULE, e.g. `FREQ=WEEKLY;COUNT=10`
+    pub rule: Option<String>,
+    /// Additional occurrences (RDATE)
+    pub dates: Vec<CalendarTime>,
+    /// Excluded occurrences (EXDATE)
+    pub exceptions: Vec<CalendarTime>,
+    /// The occurrence this event replaces (RECURRENCE-ID)
+    pub id: Option<CalendarTime>,
+}
+
+impl Recurrence {
+    /// Whether the event has more than one occurrence
+    pub fn is_recurring(&self) -> bool {
+        self.rule.is_some() || !self.dates.is_empty()
+    }
+}
+
+impl Event {
+    /// The start in `zone`, dates and floating times are interpreted in `zone` as well
+    pub fn start_in(&self, zone: Tz) -> Option<DateTime<Tz>> {
+        self.start
+            .map(|start| start.to_utc(zone).with_timezone(&zone))
+    }
+
+    /// The end in `zone`, dates and floating times are interpreted in `zone` as well
+    pub fn end_in(&self, zone: Tz) -> Option<DateTime<Tz>> {
+        self.end.map(|end| end.to_utc(zone).with_timezone(&zone))
+    }
+
+    /// Parses `event`, resolving TZIDs with the VTIMEZONEs of its calendar
+    ///
+    /// Fails if a date-time (e.g. DTSTART) or the DURATION is invalid
+    pub fn parse(event: &IcalEvent, timezones: &Timezones) -> Result<Self, Error> {
+        let times = |name| -> Result<Vec<CalendarTime>, Error> {
+            let mut times = Vec::new();
+            for property in event.properties.iter().filter(|prop| prop.name == name) {
+                let tzid = param(property, "TZID");
+                for value in property.value.as_deref().unwrap_or_default().split(',') {
+                    // RDATE can also contain periods, of which only the start matters
+                    let start = value.split('/').next().unwrap_or(value);
+                    times.push(CalendarTime::parse(start, tzid, timezones)?);
+                }
+            }
+            Ok(times)
+        };
+        let without_times = Event::without_times(event);
+
+        Ok(Event {
+            start: event
+                .get_property("DTSTART")
+                .map(|prop| CalendarTime::from_property(prop, timezones))
+                .transpose()?,
+            end: datetime::event_end(event, timezones)?,
+            recurrence: Recurrence {
+                dates: times("RDATE")?,
+                exceptions: times("EXDATE")?,
+                id: event
+                    .get_property("RECURRENCE-ID")
+                    .map(|prop| CalendarTime::from_property(prop, timezones))
+                    .transpose()?,
+                ..without_times.recurrence
+            },
+            ..without_times
+        })
+    }
+
+    /// The properties of `event` which can't be invalid, leaving out the start, end and the dates of
+    /// the recurrence, e.g. for events [Event::parse] rejects
+    pub fn without_times(event: &IcalEvent) -> Self {
+        let text = |name| {
+            event
+                .get_property(name)
+                .and_then(|prop| prop.value.as_deref())
+                .map(unescape_text)
+        };
+
+        Event {
+            uid: event
+                .get_property("UID")
+                .and_then(|prop| prop.value.clone()),
+            summary: text("SUMMARY"),
+            description: text("DESCRIPTION"),
+            location: text("LOCATION"),
+            start: None,
+            end: None,
+            status: event
+                .get_property("STATUS")
+                .and_then(|prop| prop.value.as_deref())
+                .map(EventStatus::parse),
+            url: event
+                .get_property("URL")
+                .and_then(|prop| prop.value.clone()),
+            categories: event
+                .properties
+                .iter()
+                .filter(|prop| prop.name == "CATEGORIES")
+                .filter_map(|prop| prop.value.as_deref())
+                .flat_map(split_text_list)
+                .collect(),
+            organizer: event
+                .get_property("ORGANIZER")
+                .and_then(Participant::from_property),
+            attendees: event
+                .properties
+                .iter()
+                .filter(|prop| prop.name == "ATTENDEE")
+                .filter_map(Participant::from_property)
+                .collect(),
+            recurrence: Recurrence {
+                rule: event
+                    .get_property("RRULE")
+                    .and_then(|prop| prop.value.clone()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl TryFrom<&IcalEvent> for Event {
+    type Error = Error;
+
+    /// Like [Event::parse], but only knows the time zones of the IANA database
+    fn try_from(event: &IcalEvent) -> Result<Self, Self::Error> {
+        Event::parse(event, &Timezones::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::testing;
+
+    fn parse(properties: &str) -> Result<Event, Error> {
+        Event::try_from(&testing::event(properties))
+    }
+
+    #[test]
+    fn typed_properties() {
+        let event = parse(
+            "UID:1\nSUMMARY:Exam\\; Analysis\nDESCRIPTION:Line 1\\nLine 2\nLOCATION:MI HS 1\\, Garching\n\
+             DTSTART:20250303T090000Z\nDURATION:PT2H\nSTATUS:cancelled\nCATEGORIES:Exam,Lecture\n\
+             ORGANIZER;CN=\"Prof. Doe\":mailto:doe@tum.de\n\
+             ATTENDEE;ROLE=REQ-PARTICIPANT;PARTSTAT=ACCEPTED:MAILTO:student@tum.de\n\
+             RRULE:FREQ=WEEKLY;COUNT=3\nEXDATE:20250310T090000Z,20250317T090000Z\n",
+        )
+        .unwrap();
+
+        assert_eq!(event.summary.as_deref(), Some("Exam; Analysis"));
+        assert_eq!(event.description.as_deref(), Some("Line 1\nLine 2"));
+        assert_eq!(event.location.as_deref(), Some("MI HS 1, Garching"));
+        assert_eq!(
+            event.end,
+            Some(CalendarTime::DateTime(
+                Utc.with_ymd_and_hms(2025, 3, 3, 11, 0, 0).unwrap()
+            ))
+        );
+        assert_eq!(event.status, Some(EventStatus::Cancelled));
+        assert_eq!(event.categories, vec!["Exam", "Lecture"]);
+
+        let organizer = event.organizer.unwrap();
+        assert_eq!(organizer.address, "doe@tum.de");
+        assert_eq!(organizer.name.as_deref(), Some("Prof. Doe"));
+        assert_eq!(event.attendees[0].address, "student@tum.de");
+        assert_eq!(event.attendees[0].status.as_deref(), Some("ACCEPTED"));
+
+        assert!(event.recurrence.is_recurring());
+        assert_eq!(event.recurrence.exceptions.len(), 2);
+    }
+
+    #[test]
+    fn invalid_times_are_errors() {
+        assert!(parse("UID:1\nDTSTART:2025-03-03\n").is_err());
+        assert_eq!(parse("UID:1\n").unwrap().start, None);
+    }
+
+    #[test]
+    fn texts_of_events_with_invalid_times() {
+        let properties = "UID:1\nSUMMARY:Analysis\nSTATUS:CANCELLED\nDTSTART:20250303T100000Z\n\
+                          DURATION:P200000000000000D\nRRULE:FREQ=WEEKLY\nEXDATE:2025-03-10\n\
+                          RECURRENCE-ID:invalid\n";
+        assert!(parse(properties).is_err());
+
+        let event = Event::without_times(&testing::event(properties));
+        assert_eq!(event.summary.as_deref(), Some("Analysis"));
+        assert_eq!(event.status, Some(EventStatus::Cancelled));
+        assert_eq!(event.recurrence.rule.as_deref(), Some("FREQ=WEEKLY"));
+        assert_eq!((event.start, event.end), (None, None));
+    }
+}
@@ -0,0 +1,84 @@
+//! Holding back diffs which delete a large part of a calendar.
+//!
+//! A truncated or empty (but valid) feed would otherwise be reported as the deletion of every event,
+//! wiping mirrors like the Google Calendar of [tum_google_sync](crate::tum_google_sync).
+//!
+//! See [DeletionGuard] and [ICSWatcher::set_deletion_hook](crate::ICSWatcher::set_deletion_hook).
+
+/// When a [CalendarChangeDetector](crate::CalendarChangeDetector) holds back a diff,
+/// see [DetectorConfig::deletion_guard](crate::DetectorConfig::deletion_guard).
+///
+/// A held diff isn't reported and the previous state is kept. Once the deletion was seen on
+/// `confirmations` consecutive polls, it is applied anyway.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeletionGuard {
+    /// Largest share (`0.0` to `1.0`) of the tracked components a diff may delete
+    pub max_share: Option<f64>,
+    /// Largest number of components a diff may delete
+    pub max_count: Option<usize>,
+    /// Consecutive polls after which a held deletion is applied
+    pub confirmations: u32,
+}
+
+impl Default for DeletionGuard {
+    fn default() -> Self {
+        DeletionGuard {
+            max_share: Some(0.5),
+            max_count: None,
+            confirmations: 3,
+        }
+    }
+}
+
+impl DeletionGuard {
+    /// Whether deleting `deleted` of `tracked` components has to be held back
+    pub fn exceeded(&self, deleted: usize, tracked: usize) -> bool {
+        deleted > 0
+            && (self.max_count.is_some_and(|max| deleted > max)
+                || self
+                    .max_share
+                    .is_some_and(|share| deleted as f64 > share * tracked as f64))
+    }
+}
+
+/// A deletion which exceeded the [DeletionGuard], passed to the [DeletionHook]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeldDeletion {
+    /// Components the diff deletes
+    pub deleted: usize,
+    /// Components tracked before the diff
+    pub tracked: usize,
+    /// Consecutive polls the deletion was seen on, including this one
+    pub polls: u32,
+    /// Whether the deletion was applied, as it was seen on [DeletionGuard::confirmations] polls
+    pub applied: bool,
+}
+
+/// Called with the name of the calendar whenever a deletion exceeds the [DeletionGuard]
+pub type DeletionHook = Box<dyn Fn(Option<&str>, &HeldDeletion)>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits() {
+        let guard = DeletionGuard {
+            max_share: Some(0.5),
+            max_count: Some(10),
+            confirmations: 3,
+        };
+
+        assert!(!guard.exceeded(5, 10));
+        assert!(guard.exceeded(6, 10));
+        assert!(guard.exceeded(11, 100));
+        assert!(!guard.exceeded(0, 0));
+
+        let unlimited = DeletionGuard {
+            max_share: None,
+            max_count: None,
+            confirmations: 3,
+        };
+        assert!(!unlimited.exceeded(100, 100));
+    }
+}
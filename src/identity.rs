@@ -0,0 +1,176 @@
+//! Identifying components without a UID.
+//!
+//! Some exporters omit the UID of their events. Such components are skipped by default,
+//! [IdentityStrategy::ContentHash] and [IdentityStrategy::Positional] generate a replacement
+//! so they can still be tracked.
+//!
+//! The generated identities only stay the same as long as the strategy does, which is why
+//! it is part of the backup (see [ICSWatcher::load_backup](crate::ICSWatcher::load_backup)).
+
+use std::collections::HashMap;
+
+use ical::parser::{ical::component::IcalEvent, Component};
+use serde::{Deserialize, Serialize};
+
+use crate::ComponentKind;
+
+/// Prefix of all generated identities, which can't collide with real UIDs,
+/// as the parser strips the colons at the start of every value
+const GENERATED_PREFIX: &str = ":generated-";
+
+/// How components without a UID are identified, see [DetectorConfig::missing_uid](crate::DetectorConfig::missing_uid)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdentityStrategy {
+    /// Don't track components without a UID
+    #[default]
+    Skip,
+    /// Identify them by their DTSTART, SUMMARY and LOCATION.
+    ///
+    /// Changing one of these properties is reported as a deletion and a creation.
+    ContentHash,
+    /// Identify them by their position among the components without a UID in the calendar.
+    ///
+    /// Inserting or removing one of them is reported as changes of all following ones.
+    Positional,
+}
+
+/// Generates the identities of the components without a UID of one calendar
+pub(crate) struct Identities {
+    strategy: IdentityStrategy,
+    /// How often an identity was generated, to tell identical components apart
+    generated: HashMap<(ComponentKind, String), usize>,
+}
+
+impl Identities {
+    pub(crate) fn new(strategy: IdentityStrategy) -> Self {
+        Identities {
+            strategy,
+            generated: HashMap::new(),
+        }
+    }
+
+    /// The identity of a component without a UID, [None] if it shouldn't be tracked
+    pub(crate) fn generate(
+        &mut self,
+        kind: ComponentKind,
+        component: &IcalEvent,
+    ) -> Option<String> {
+        let base = match self.strategy {
+            IdentityStrategy::Skip => return None,
+            IdentityStrategy::ContentHash => format!("{:016x}", content_hash(component)),
+            IdentityStrategy::Positional => String::new(),
+        };
+
+        let count = self.generated.entry((kind, base.clone())).or_default();
+        let index = *count;
+        *count += 1;
+
+        Some(match self.strategy {
+            IdentityStrategy::Positional => format!("{GENERATED_PREFIX}#{index}"),
+            _ if index == 0 => format!("{GENERATED_PREFIX}{base}"),
+            _ => format!("{GENERATED_PREFIX}{base}-{index}"),
+        })
+    }
+}
+
+/// Whether the component tracked as `key` (see [ComponentKind]) was identified by a generated identity
+pub(crate) fn is_generated(key: &str) -> bool {
+    key.starts_with(GENERATED_PREFIX)
+        || key
+            .split_once(':')
+            .is_some_and(|(_, uid)| uid.starts_with(GENERATED_PREFIX))
+}
+
+/// FNV-1a hash of DTSTART, SUMMARY and LOCATION, which (unlike [std::hash::DefaultHasher])
+/// is the same across releases and platforms
+fn content_hash(component: &IcalEvent) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for name in ["DTSTART", "SUMMARY", "LOCATION"] {
+        let value = component
+            .get_property(name)
+            .and_then(|prop| prop.value.as_deref())
+            .unwrap_or_default();
+        for byte in value.bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::event;
+
+    #[test]
+    fn content_hash_is_stable() {
+        let lecture =
+            event("DTSTART:20250303T100000Z\nSUMMARY:Analysis\nDESCRIPTION:Room changed\n");
+        let mut identities = Identities::new(IdentityStrategy::ContentHash);
+        let first = identities.generate(ComponentKind::Event, &lecture).unwrap();
+
+        assert_eq!(first, ":generated-c96a11b6a414ffab");
+        // Identical components are told apart by their order
+        assert_eq!(
+            identities.generate(ComponentKind::Event, &lecture).unwrap(),
+            format!("{first}-1")
+        );
+        // Only DTSTART, SUMMARY and LOCATION matter
+        let mut identities = Identities::new(IdentityStrategy::ContentHash);
+        assert_eq!(
+            identities
+                .generate(
+                    ComponentKind::Event,
+                    &event("DTSTART:20250303T100000Z\nSUMMARY:Analysis\n")
+                )
+                .unwrap(),
+            first
+        );
+    }
+
+    #[test]
+    fn positional_and_skip() {
+        let lecture = event("SUMMARY:Analysis\n");
+        let mut identities = Identities::new(IdentityStrategy::Positional);
+        assert_eq!(
+            identities
+                .generate(ComponentKind::Event, &lecture)
+                .as_deref(),
+            Some(":generated-#0")
+        );
+        assert_eq!(
+            identities
+                .generate(ComponentKind::Todo, &lecture)
+                .as_deref(),
+            Some(":generated-#0")
+        );
+        assert_eq!(
+            identities
+                .generate(ComponentKind::Event, &lecture)
+                .as_deref(),
+            Some(":generated-#1")
+        );
+
+        let mut identities = Identities::new(IdentityStrategy::Skip);
+        assert_eq!(identities.generate(ComponentKind::Event, &lecture), None);
+    }
+
+    #[test]
+    fn real_uids_are_never_generated() {
+        let mut identities = Identities::new(IdentityStrategy::Positional);
+        let generated = identities
+            .generate(ComponentKind::Event, &event("SUMMARY:Analysis\n"))
+            .unwrap();
+        assert!(is_generated(&generated));
+        assert!(is_generated(&ComponentKind::Todo.key(generated.clone())));
+
+        let uid = event(&format!("UID:{generated}\n"))
+            .get_property("UID")
+            .and_then(|prop| prop.value.clone())
+            .unwrap();
+        assert_ne!(uid, generated);
+        assert!(!is_generated(&uid));
+        assert!(!is_generated(&ComponentKind::Todo.key(uid)));
+    }
+}
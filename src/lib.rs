@@ -8,17 +8,19 @@ use std::{
     fs::{self, File},
     future::Future,
     io::BufReader,
-    path::Path,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
     pin::Pin,
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
-use chrono::{NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
 
 use ical::{
     parser::{
-        ical::component::{IcalCalendar, IcalEvent},
+        ical::component::{IcalAlarm, IcalCalendar, IcalEvent, IcalTodo},
         Component,
     },
     property::Property,
@@ -26,7 +28,7 @@ use ical::{
 };
 
 use google_calendar3::{
-    api::{Event, EventDateTime},
+    api::{Event as GoogleEvent, EventDateTime},
     hyper_rustls::{self, HttpsConnector},
     hyper_util::{self, client::legacy::connect::HttpConnector},
     yup_oauth2::{self, read_application_secret},
@@ -36,112 +38,152 @@ use google_calendar3::{
 use once_cell::sync::Lazy;
 use regex::Regex;
 use sanitize_filename::sanitize;
+use serde::{Deserialize, Serialize};
 use tokio::time::sleep;
-
-fn rfc5545_to_std_duration(rfc_duration: &str) -> Duration {
-    let duration_str = rfc_duration.trim_start_matches('P').replace('T', "");
-
-    let mut total_secs = 0u64;
-    let mut number = String::new();
-
-    for c in duration_str.chars() {
-        match c {
-            'W' => {
-                let weeks = number.parse::<u64>().unwrap_or(0);
-                total_secs += weeks * 7 * 24 * 60 * 60;
-                number.clear();
-            }
-            'D' => {
-                let days = number.parse::<u64>().unwrap_or(0);
-                total_secs += days * 24 * 60 * 60;
-                number.clear();
-            }
-            'H' => {
-                let hours = number.parse::<u64>().unwrap_or(0);
-                total_secs += hours * 60 * 60;
-                number.clear();
-            }
-            'M' => {
-                let minutes = number.parse::<u64>().unwrap_or(0);
-                total_secs += minutes * 60;
-                number.clear();
-            }
-            'S' => {
-                let seconds = number.parse::<u64>().unwrap_or(0);
-                total_secs += seconds;
-                number.clear();
-            }
-            digit if digit.is_ascii_digit() => {
-                number.push(digit);
-            }
-            '-' => {}
-            _ => {}
-        }
-    }
-
-    Duration::from_secs(total_secs)
+use tracing::{debug, info, info_span, instrument, warn, Instrument};
+
+pub mod changeset;
+pub mod datetime;
+pub mod debounce;
+pub mod diff;
+pub mod error;
+pub mod event;
+pub mod guard;
+pub mod identity;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod recurrence;
+pub mod retry;
+pub mod source;
+pub mod supervisor;
+#[cfg(test)]
+mod testing;
+
+pub use changeset::ChangeSet;
+pub use datetime::{CalendarDuration, CalendarTime, Timezones};
+pub use debounce::{Debounce, PendingChange};
+pub use diff::{IgnoredProperties, StalenessPolicy};
+pub use error::Error;
+pub use event::Event;
+pub use guard::{DeletionGuard, DeletionHook, HeldDeletion};
+use identity::Identities;
+pub use identity::IdentityStrategy;
+pub use recurrence::RecurrenceExpansion;
+pub use retry::{ErrorHook, RetryPolicy, RetryStatus};
+pub use source::{CacheValidators, CalendarSource, FetchedCalendar, FileSource, HttpSource};
+pub use supervisor::{FeedCallback, ICSSupervisor};
+
+/// Refresh interval of calendars which don't publish one (X-PUBLISHED-TTL)
+const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Directory backups are saved in, unless [ICSWatcher::set_backup_dir] is used
+const DEFAULT_BACKUP_DIR: &str = ".backups";
+
+/// Converts a DURATION value, [None] if it is malformed or negative
+fn rfc5545_to_std_duration(rfc_duration: &str) -> Option<Duration> {
+    CalendarDuration::parse(rfc_duration)
+        .ok()?
+        .to_time_delta()?
+        .to_std()
+        .ok()
 }
 
-fn changed_properties(event1: &IcalEvent, event2: &IcalEvent) -> Option<Vec<String>> {
-    let props1 = &event1.properties;
-    let props2 = &event2.properties;
-
-    let mut changed_props = Vec::new();
+/// The type of a calendar component, see [DetectorConfig::components]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentKind {
+    /// VEVENT
+    Event,
+    /// VTODO, e.g. assignment deadlines
+    Todo,
+    /// VJOURNAL
+    Journal,
+    /// VFREEBUSY
+    FreeBusy,
+}
 
-    // Check for modified and removed properties
-    for prop1 in props1.iter().filter(|p| p.name != "DTSTAMP") {
-        let matching_prop = props2
-            .iter()
-            .filter(|p| p.name != "DTSTAMP")
-            .find(|prop2| prop1.name == prop2.name);
-
-        match matching_prop {
-            Some(prop2) => {
-                // Check if existing property changed
-                if prop1.value != prop2.value
-                    || match (&prop1.params, &prop2.params) {
-                        (Some(params1), Some(params2)) => params1 != params2,
-                        (None, None) => false,
-                        _ => true,
-                    }
-                {
-                    changed_props.push(prop1.name.clone());
-                }
-            }
-            None => {
-                // Property was removed in event2
-                changed_props.push(prop1.name.clone());
-            }
+impl ComponentKind {
+    /// The name of the component in iCalendar, e.g. `VTODO`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ComponentKind::Event => "VEVENT",
+            ComponentKind::Todo => "VTODO",
+            ComponentKind::Journal => "VJOURNAL",
+            ComponentKind::FreeBusy => "VFREEBUSY",
         }
     }
 
-    // Check for new properties in event2
-    for prop2 in props2.iter().filter(|p| p.name != "DTSTAMP") {
-        if !props1.iter().any(|p| p.name == prop2.name) {
-            changed_props.push(prop2.name.clone());
+    /// Events are identified by their UID, all other components by their UID prefixed with their kind (e.g. `VTODO:`)
+    fn key(&self, uid: String) -> String {
+        match self {
+            ComponentKind::Event => uid,
+            kind => format!("{}:{uid}", kind.name()),
         }
     }
 
-    if changed_props.is_empty() {
-        None
-    } else {
-        Some(changed_props)
+    /// The kind of the component identified by `key`, see [ComponentKind::key].
+    ///
+    /// Only used for states restored without their kinds, as events can have UIDs like `VTODO:1` as well.
+    fn of_key(key: &str) -> Self {
+        [
+            ComponentKind::Todo,
+            ComponentKind::Journal,
+            ComponentKind::FreeBusy,
+        ]
+        .into_iter()
+        .find(|kind| {
+            key.strip_prefix(kind.name())
+                .is_some_and(|rest| rest.starts_with(':'))
+        })
+        .unwrap_or(ComponentKind::Event)
     }
 }
 
-/// A helper struct to save an [IcalEvent] with its uid
+/// A helper struct to save an [IcalEvent] with its uid.
+///
+/// Other components than events (see [`EventData::kind`]) are stored as [IcalEvent] as well,
+/// with their properties and alarms. Their uid is prefixed with their kind, e.g. `VTODO:`.
+///
+/// It is serialized along with the VTIMEZONEs it references, see [ChangeSet] for the format.
 #[derive(Debug, Clone)]
 pub struct EventData {
     pub uid: String,
+    pub kind: ComponentKind,
     pub ical_data: IcalEvent,
+    /// The VTIMEZONEs of the calendar the event is part of
+    pub timezones: Timezones,
+}
+
+impl EventData {
+    fn new(uid: String, kind: ComponentKind, ical_data: IcalEvent, timezones: Timezones) -> Self {
+        EventData {
+            uid,
+            kind,
+            ical_data,
+            timezones,
+        }
+    }
+
+    /// The typed properties of the event, see [Event]
+    pub fn event(&self) -> Result<Event, Error> {
+        Event::parse(&self.ical_data, &self.timezones)
+    }
 }
 
 /// A struct denoting a Property Change of a [key](`PropertyChange::key`) with both states in [from](`PropertyChange::from`) and [to](`PropertyChange::to`).
 ///
+/// Properties like ATTENDEE or EXDATE can occur multiple times or hold several values.
+/// [from](`PropertyChange::from`) and [to](`PropertyChange::to`) only contain the first property of each state,
+/// the individual values which were [added](`PropertyChange::added`) and [removed](`PropertyChange::removed`)
+/// are listed one property per value.
+///
 /// # Examples
 ///
 /// ```
+/// # use ical::property::Property;
+/// # use ics_watcher::PropertyChange;
 /// // A description has been added with the contents "New Description"
+/// # let _ =
 /// PropertyChange {
 ///     key: "DESCRIPTION".to_string(),
 ///     from: None,
@@ -149,14 +191,43 @@ pub struct EventData {
 ///         name: "DESCRIPTION".to_string(),
 ///         params: None,
 ///         value: Some("New Description".to_string())
-///     })
+///     }),
+///     added: vec![Property {
+///         name: "DESCRIPTION".to_string(),
+///         params: None,
+///         value: Some("New Description".to_string())
+///     }],
+///     removed: vec![],
 /// }
+/// # ;
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PropertyChange {
     pub key: String,
+    #[serde(with = "changeset::wire")]
     pub from: Option<Property>,
+    #[serde(with = "changeset::wire")]
     pub to: Option<Property>,
+    #[serde(with = "changeset::wire")]
+    pub added: Vec<Property>,
+    #[serde(with = "changeset::wire")]
+    pub removed: Vec<Property>,
+}
+
+/// A change of the reminders (VALARM) of an event, see [`CalendarEvent::Updated::changed_alarms`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum AlarmChange {
+    Added(#[serde(with = "changeset::wire")] IcalAlarm),
+    Removed(#[serde(with = "changeset::wire")] IcalAlarm),
+    /// An alarm changed, e.g. its TRIGGER or ACTION
+    Updated {
+        #[serde(with = "changeset::wire")]
+        from: IcalAlarm,
+        #[serde(with = "changeset::wire")]
+        to: IcalAlarm,
+        changed_properties: Vec<PropertyChange>,
+    },
 }
 
 /// Used to pass the events to the callbacks.
@@ -164,77 +235,366 @@ pub struct PropertyChange {
 /// The types:
 /// - [`CalendarEvent::Setup`]: If the ICS Watcher is being initialized for the first time, all events that are found will be passed as [`CalendarEvent::Setup`]
 /// - [`CalendarEvent::Created`]: If the ICS Watcher has been running, any new events found will be passed as [`CalendarEvent::Created`]
-/// - [`CalendarEvent::Updated`]: Any events with different properties. The changed properties, along with both the before and after state will be passed in [`CalendarEvent::Updated::changed_properties`]
+/// - [`CalendarEvent::Updated`]: Any events with different properties. The changed properties, along with both the before and after state will be passed in [`CalendarEvent::Updated::changed_properties`],
+///   changed reminders in [`CalendarEvent::Updated::changed_alarms`]
 /// - [`CalendarEvent::Deleted`]: If an event is not found anymore, it is being passed as [`CalendarEvent::Deleted`]
-#[derive(Debug, Clone)]
+/// - [`CalendarEvent::Regressed`]: If [DetectorConfig::stale_versions] is [StalenessPolicy::Flag], an update to an older version
+///   (lower SEQUENCE or earlier LAST-MODIFIED) of an event, e.g. served by an outdated cache. The newer version is kept
+/// - [`CalendarEvent::Moved`]: If [DetectorConfig::match_moved] is set, a deleted and a created event which only differ in their UID,
+///   with the other changed properties in [`CalendarEvent::Moved::changed_properties`]
+/// - [`CalendarEvent::MetadataChanged`]: If the calendar itself changed, e.g. its name or refresh interval (see [METADATA_PROPERTIES])
+///
+/// Changes can be serialized, see [ChangeSet] for the format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
 pub enum CalendarEvent {
     Setup(EventData),
     Created(EventData),
     Updated {
         event: EventData,
         changed_properties: Vec<PropertyChange>,
+        changed_alarms: Vec<AlarmChange>,
     },
     Deleted(EventData),
+    Regressed {
+        event: EventData,
+        changed_properties: Vec<PropertyChange>,
+    },
+    Moved {
+        from: EventData,
+        to: EventData,
+        changed_properties: Vec<PropertyChange>,
+    },
+    MetadataChanged(Vec<PropertyChange>),
+}
+
+impl CalendarEvent {
+    /// The event (or other component) which changed, [None] for changes of the calendar itself
+    pub fn data(&self) -> Option<&EventData> {
+        match self {
+            CalendarEvent::Setup(data)
+            | CalendarEvent::Created(data)
+            | CalendarEvent::Updated { event: data, .. }
+            | CalendarEvent::Deleted(data)
+            | CalendarEvent::Regressed { event: data, .. }
+            | CalendarEvent::Moved { to: data, .. } => Some(data),
+            CalendarEvent::MetadataChanged(_) => None,
+        }
+    }
+}
+
+/// Calendar properties whose changes are reported as [`CalendarEvent::MetadataChanged`]
+pub const METADATA_PROPERTIES: &[&str] = &[
+    "X-WR-CALNAME",
+    "X-WR-CALDESC",
+    "X-WR-TIMEZONE",
+    "X-PUBLISHED-TTL",
+    "NAME",
+    "DESCRIPTION",
+    "REFRESH-INTERVAL",
+];
+
+/// Options of the change detection, see [ICSWatcher::set_detector_config]
+#[derive(Debug, Clone)]
+pub struct DetectorConfig {
+    /// The components which are tracked, only events by default
+    pub components: Vec<ComponentKind>,
+    /// Tracks every occurrence of a recurring event on its own instead of the event as a whole
+    pub expand_recurrences: Option<RecurrenceExpansion>,
+    /// Properties whose changes aren't reported, e.g. LAST-MODIFIED or SEQUENCE
+    pub ignored_properties: IgnoredProperties,
+    /// How components without a UID are identified, they are skipped by default.
+    ///
+    /// Calendars restored by [ICSWatcher::load_backup] keep the strategy their backup was created with
+    /// if the backup tracked components without a UID.
+    pub missing_uid: IdentityStrategy,
+    /// Reports a deleted and a created event with the same SUMMARY, DTSTART and LOCATION as [`CalendarEvent::Moved`]
+    /// instead, e.g. when the publisher regenerated its UIDs
+    pub match_moved: bool,
+    /// How updates to an older version of an event (lower SEQUENCE or earlier LAST-MODIFIED) are handled
+    pub stale_versions: StalenessPolicy,
+    /// Holds back diffs deleting a large part of the calendar, e.g. when the feed was truncated
+    pub deletion_guard: Option<DeletionGuard>,
+    /// Reports updates and deletions only once they were stable for a number of polls or some time,
+    /// e.g. for feeds alternating between two versions of an event
+    pub debounce: Option<Debounce>,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        DetectorConfig {
+            components: vec![ComponentKind::Event],
+            expand_recurrences: None,
+            ignored_properties: IgnoredProperties::default(),
+            missing_uid: IdentityStrategy::default(),
+            match_moved: false,
+            stale_versions: StalenessPolicy::default(),
+            deletion_guard: None,
+            debounce: None,
+        }
+    }
 }
 
 /// Handling change detection of a single calendar (as one ics file can contain multiple calendars)
 /// For usage details, see [ICSWatcher]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CalendarChangeDetector {
     pub name: Option<String>,
     pub description: Option<String>,
     pub ttl: Duration,
+    pub config: DetectorConfig,
     previous: HashMap<String, IcalEvent>,
+    /// The kinds of the components in `previous`, by their key
+    kinds: HashMap<String, ComponentKind>,
+    metadata: Option<Vec<Property>>,
     initialized: bool,
+    /// Consecutive polls a deletion exceeding the [DeletionGuard] was seen on
+    held_polls: u32,
+    held_deletion: Option<HeldDeletion>,
+    pending: HashMap<String, PendingChange>,
+    /// When recurring events were last expanded, see [DetectorConfig::expand_recurrences]
+    expanded_at: Option<DateTime<Utc>>,
+    /// The VTIMEZONEs of the last compared calendar
+    timezones: Timezones,
+    /// The strategy of a restored backup which tracked components without a UID,
+    /// it takes precedence over [DetectorConfig::missing_uid]
+    restored_identity: Option<IdentityStrategy>,
+}
+
+impl Default for CalendarChangeDetector {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CalendarChangeDetector {
     pub fn new() -> Self {
+        Self::with_config(DetectorConfig::default())
+    }
+
+    pub fn with_config(config: DetectorConfig) -> Self {
         CalendarChangeDetector {
             name: None,
             description: None,
-            ttl: rfc5545_to_std_duration("PT1H"),
+            ttl: DEFAULT_TTL,
+            config,
             previous: HashMap::new(),
+            kinds: HashMap::new(),
+            metadata: None,
             initialized: false,
+            held_polls: 0,
+            held_deletion: None,
+            pending: HashMap::new(),
+            expanded_at: None,
+            timezones: Timezones::default(),
+            restored_identity: None,
         }
     }
 
+    /// Restores the components changes are detected against, by their key.
+    ///
+    /// Their kinds are derived from the `VTODO:`-like prefix of their key.
     pub fn set_state(&mut self, state: HashMap<String, IcalEvent>) {
+        self.kinds = state
+            .keys()
+            .map(|key| (key.clone(), ComponentKind::of_key(key)))
+            .collect();
         self.previous = state;
         self.initialized = true;
     }
 
+    /// The kind of the tracked component identified by `key`
+    fn kind_of(&self, key: &str) -> ComponentKind {
+        self.kinds
+            .get(key)
+            .copied()
+            .unwrap_or_else(|| ComponentKind::of_key(key))
+    }
+
+    pub fn get_state(&self) -> &HashMap<String, IcalEvent> {
+        &self.previous
+    }
+
+    /// Restores the calendar properties (see [METADATA_PROPERTIES]) changes are reported against
+    pub fn set_metadata(&mut self, metadata: Vec<Property>) {
+        self.metadata = Some(metadata);
+    }
+
+    /// The calendar properties (see [METADATA_PROPERTIES]) of the last comparison, [None] if unknown
+    pub fn get_metadata(&self) -> Option<&[Property]> {
+        self.metadata.as_deref()
+    }
+
+    /// Restores the changes which weren't stable enough to be reported yet, see [DetectorConfig::debounce]
+    pub fn set_pending(&mut self, pending: HashMap<String, PendingChange>) {
+        self.pending = pending;
+    }
+
+    /// The changes which weren't stable enough to be reported yet, by the key of their event
+    pub fn get_pending(&self) -> &HashMap<String, PendingChange> {
+        &self.pending
+    }
+
+    /// The deletion the [DeletionGuard] caught in the last comparison, if any
+    pub fn held_deletion(&self) -> Option<&HeldDeletion> {
+        self.held_deletion.as_ref()
+    }
+
+    /// Forgets all events, reporting them as [`CalendarEvent::Deleted`].
+    ///
+    /// Used when the calendar isn't part of the ics file anymore.
+    /// Nothing is forgotten if the [DeletionGuard] holds the deletion back (see [CalendarChangeDetector::held_deletion]).
+    pub fn clear(&mut self) -> Vec<CalendarEvent> {
+        if self.hold_deletion(self.previous.len(), self.previous.len()) {
+            return Vec::new();
+        }
+
+        let previous = std::mem::take(&mut self.previous);
+        let deleted = previous
+            .into_iter()
+            .map(|(uid, ical_data)| {
+                let kind = self.kind_of(&uid);
+                CalendarEvent::Deleted(EventData::new(uid, kind, ical_data, self.timezones.clone()))
+            })
+            .collect();
+        self.kinds.clear();
+        deleted
+    }
+
     pub fn compare(&mut self, calendar: IcalCalendar) -> Vec<CalendarEvent> {
-        self.name = calendar
+        self.compare_at(calendar, Utc::now())
+    }
+
+    /// Compares `calendar` to the previous state at the time `now`
+    #[instrument(name = "diff", skip_all, fields(calendar = tracing::field::Empty))]
+    pub(crate) fn compare_at(
+        &mut self,
+        calendar: IcalCalendar,
+        now: DateTime<Utc>,
+    ) -> Vec<CalendarEvent> {
+        // Only taken over once the comparison isn't held back by the deletion guard
+        let timezones = Timezones::of(&calendar);
+        let name = calendar
             .get_property("X-WR-CALNAME")
             .and_then(|prop| prop.value.clone());
-
-        self.description = calendar
+        if let Some(name) = &name {
+            tracing::Span::current().record("calendar", name.as_str());
+        }
+        let description = calendar
             .get_property("X-WR-CALDESC")
             .and_then(|prop| prop.value.clone());
-
-        self.ttl = calendar
+        let ttl = calendar
             .get_property("X-PUBLISHED-TTL")
             .and_then(|prop| prop.value.as_ref())
-            .map(|value| value.as_str())
-            .and_then(|s| Some(rfc5545_to_std_duration(s)))
-            .unwrap_or_else(|| rfc5545_to_std_duration("PT1H"));
+            .and_then(|value| {
+                let ttl = rfc5545_to_std_duration(value).filter(|ttl| !ttl.is_zero());
+                if ttl.is_none() {
+                    warn!(value, "Ignoring invalid X-PUBLISHED-TTL");
+                }
+                ttl
+            })
+            .unwrap_or(DEFAULT_TTL);
+
+        let mut result = Vec::with_capacity(calendar.events.len() + 1);
+
+        let metadata: Vec<Property> = calendar
+            .properties
+            .iter()
+            .filter(|prop| METADATA_PROPERTIES.contains(&prop.name.as_str()))
+            .cloned()
+            .collect();
+        if let (true, Some(previous)) = (self.initialized, &self.metadata) {
+            let changes =
+                diff::property_changes(previous, &metadata, &IgnoredProperties::none(), &timezones);
+            if !changes.is_empty() {
+                result.push(CalendarEvent::MetadataChanged(changes));
+            }
+        }
+        let previous_metadata = self.metadata.replace(metadata);
+
+        // The occurrences of recurring events tracked by the previous and by this comparison
+        let expansion = self.config.expand_recurrences.as_ref();
+        let window = expansion.map(|expansion| expansion.window(now.naive_utc()));
+        let previous_window = expansion
+            .map(|expansion| expansion.window(self.expanded_at.unwrap_or(now).naive_utc()));
+        // Occurrences outside a window aren't tracked, so them entering or leaving it isn't a change
+        let outside = |window: &Option<RangeInclusive<NaiveDateTime>>,
+                       kind: ComponentKind,
+                       event: &IcalEvent| {
+            kind == ComponentKind::Event
+                && window
+                    .as_ref()
+                    .zip(recurrence::recurrence_id(event, &timezones))
+                    .is_some_and(|(window, at)| !window.contains(&at.naive_utc()))
+        };
+
+        let tracks = |kind| self.config.components.contains(&kind);
+        let mut components: Vec<(ComponentKind, IcalEvent)> = Vec::new();
+        if tracks(ComponentKind::Event) {
+            let events = match expansion {
+                Some(expansion) => {
+                    recurrence::expand(calendar.events, expansion, now.naive_utc(), &timezones)
+                }
+                None => calendar.events,
+            };
+            components.extend(
+                events
+                    .into_iter()
+                    .map(|event| (ComponentKind::Event, event)),
+            );
+        }
+        if tracks(ComponentKind::Todo) {
+            components.extend(calendar.todos.into_iter().map(|todo| {
+                let IcalTodo { properties, alarms } = todo;
+                (ComponentKind::Todo, IcalEvent { properties, alarms })
+            }));
+        }
+        if tracks(ComponentKind::Journal) {
+            components.extend(calendar.journals.into_iter().map(|journal| {
+                let properties = journal.properties;
+                (
+                    ComponentKind::Journal,
+                    IcalEvent {
+                        properties,
+                        alarms: vec![],
+                    },
+                )
+            }));
+        }
+        if tracks(ComponentKind::FreeBusy) {
+            components.extend(calendar.free_busys.into_iter().map(|free_busy| {
+                let properties = free_busy.properties;
+                (
+                    ComponentKind::FreeBusy,
+                    IcalEvent {
+                        properties,
+                        alarms: vec![],
+                    },
+                )
+            }));
+        }
 
         let mut new_previous = HashMap::new();
-        let mut result = Vec::with_capacity(calendar.events.len());
+        let mut new_kinds = HashMap::new();
+        let mut identities = Identities::new(self.config.missing_uid);
 
-        for event in calendar.events {
+        for (kind, event) in components {
             let event_uid_property = match event
                 .get_property("UID")
                 .and_then(|prop| prop.value.clone())
+                .or_else(|| identities.generate(kind, &event))
             {
                 Some(uid) => uid,
                 None => {
-                    println!("Warning: An event is missing a UID, skipping");
+                    warn!(
+                        component = kind.name(),
+                        summary = ?event.get_property("SUMMARY").and_then(|prop| prop.value.as_deref()),
+                        "A component is missing a UID, skipping"
+                    );
                     continue;
                 }
             };
-            let event_uid = event_uid_property
+            let event_uid = kind.key(event_uid_property)
                 + &event
                     .get_property("RECURRENCE-ID")
                     .map(|prop| match prop.value.clone() {
@@ -251,56 +611,239 @@ impl CalendarChangeDetector {
                     .unwrap_or(String::from(""));
 
             new_previous.insert(event_uid.clone(), event.clone());
+            new_kinds.insert(event_uid.clone(), kind);
             if self.initialized {
                 if let Some(prev_event) = self.previous.get(&event_uid) {
-                    match changed_properties(prev_event, &event) {
-                        Some(properties) => {
-                            result.push(CalendarEvent::Updated {
-                                changed_properties: properties
-                                    .iter()
-                                    .map(|property| PropertyChange {
-                                        key: property.clone(),
-                                        from: self.previous[&event_uid]
-                                            .get_property(property)
-                                            .cloned(),
-                                        to: new_previous[&event_uid]
-                                            .get_property(property)
-                                            .cloned(),
-                                    })
-                                    .collect(),
-                                event: EventData {
-                                    uid: event_uid,
-                                    ical_data: event,
-                                },
-                            });
+                    let ignored = &self.config.ignored_properties;
+                    let changed_properties =
+                        diff::event_changes(prev_event, &event, ignored, &timezones);
+                    let changed_alarms =
+                        diff::alarm_changes(prev_event, &event, ignored, &timezones);
+
+                    let stale = self.config.stale_versions != StalenessPolicy::Report
+                        && diff::is_regression(prev_event, &event, &timezones);
+                    if stale {
+                        // Keep the newer version, so the feed catching up again isn't a change
+                        new_previous.insert(event_uid.clone(), prev_event.clone());
+                    }
+
+                    if !changed_properties.is_empty() || !changed_alarms.is_empty() {
+                        let event = EventData {
+                            uid: event_uid,
+                            kind,
+                            ical_data: event,
+                            timezones: timezones.clone(),
+                        };
+                        match (stale, self.config.stale_versions) {
+                            (false, _) | (true, StalenessPolicy::Report) => {
+                                result.push(CalendarEvent::Updated {
+                                    changed_properties,
+                                    changed_alarms,
+                                    event,
+                                })
+                            }
+                            (true, StalenessPolicy::Flag) => {
+                                result.push(CalendarEvent::Regressed {
+                                    event,
+                                    changed_properties,
+                                })
+                            }
+                            (true, StalenessPolicy::Suppress) => {
+                                debug!(uid = event.uid, "Ignoring stale version")
+                            }
                         }
-                        None => (),
                     }
+                } else if outside(&previous_window, kind, &event) {
+                    debug!(uid = event_uid, "Occurrence entered the expansion window");
                 } else {
                     result.push(CalendarEvent::Created(EventData {
                         uid: event_uid,
+                        kind,
                         ical_data: event,
+                        timezones: timezones.clone(),
                     }));
                 }
             } else {
                 result.push(CalendarEvent::Setup(EventData {
                     uid: event_uid,
+                    kind,
                     ical_data: event,
+                    timezones: timezones.clone(),
                 }));
             }
         }
 
-        for (uid, ical_data) in self.previous.drain() {
-            if !new_previous.contains_key(&uid) {
-                result.push(CalendarEvent::Deleted(EventData { uid, ical_data }));
+        let previous = std::mem::take(&mut self.previous);
+        let mut deleted: Vec<EventData> = previous
+            .iter()
+            .filter(|(uid, _)| !new_previous.contains_key(*uid))
+            .map(|(uid, ical_data)| {
+                EventData::new(
+                    uid.clone(),
+                    self.kind_of(uid),
+                    ical_data.clone(),
+                    timezones.clone(),
+                )
+            })
+            .filter(|data| !outside(&window, data.kind, &data.ical_data))
+            .collect();
+
+        if self.config.match_moved {
+            for change in &mut result {
+                let CalendarEvent::Created(created) = change else {
+                    continue;
+                };
+                let Some(index) = deleted.iter().position(|from| {
+                    from.kind == created.kind
+                        && diff::is_moved(&from.ical_data, &created.ical_data, &timezones)
+                }) else {
+                    continue;
+                };
+
+                let from = deleted.remove(index);
+                let mut changed_properties = diff::event_changes(
+                    &from.ical_data,
+                    &created.ical_data,
+                    &self.config.ignored_properties,
+                    &timezones,
+                );
+                changed_properties.retain(|change| change.key != "UID");
+                *change = CalendarEvent::Moved {
+                    from,
+                    to: created.clone(),
+                    changed_properties,
+                };
             }
         }
 
+        if self.hold_deletion(deleted.len(), previous.len()) {
+            self.previous = previous;
+            self.metadata = previous_metadata;
+            return Vec::new();
+        }
+        result.extend(deleted.into_iter().map(CalendarEvent::Deleted));
+        self.timezones = timezones;
+        self.name = name;
+        self.description = description;
+        self.ttl = ttl;
+
+        if let (true, Some(debounce)) = (self.initialized, self.config.debounce) {
+            result = debounce::settle(
+                debounce,
+                &mut self.pending,
+                result,
+                &previous,
+                &mut new_previous,
+                &self.config.ignored_properties,
+                SystemTime::from(now),
+            );
+        }
+
+        // Components whose deletion is pending keep their kind
+        for (key, kind) in std::mem::take(&mut self.kinds) {
+            if new_previous.contains_key(&key) {
+                new_kinds.entry(key).or_insert(kind);
+            }
+        }
         self.previous = new_previous;
+        self.kinds = new_kinds;
         self.initialized = true;
+        self.expanded_at = window.is_some().then_some(now);
+
+        let count = |kind: fn(&CalendarEvent) -> bool| result.iter().filter(|e| kind(e)).count();
+        debug!(
+            setup = count(|e| matches!(e, CalendarEvent::Setup(_))),
+            created = count(|e| matches!(e, CalendarEvent::Created(_))),
+            updated = count(|e| matches!(e, CalendarEvent::Updated { .. })),
+            deleted = count(|e| matches!(e, CalendarEvent::Deleted(_))),
+            regressed = count(|e| matches!(e, CalendarEvent::Regressed { .. })),
+            moved = count(|e| matches!(e, CalendarEvent::Moved { .. })),
+            metadata = count(|e| matches!(e, CalendarEvent::MetadataChanged(_))),
+            "Compared calendar"
+        );
 
         result
     }
+
+    /// Whether deleting `deleted` of `tracked` components has to be held back, see [DetectorConfig::deletion_guard]
+    fn hold_deletion(&mut self, deleted: usize, tracked: usize) -> bool {
+        self.held_deletion = None;
+        let Some(guard) = &self.config.deletion_guard else {
+            return false;
+        };
+        if !self.initialized || !guard.exceeded(deleted, tracked) {
+            self.held_polls = 0;
+            return false;
+        }
+
+        self.held_polls += 1;
+        let polls = self.held_polls;
+        let applied = polls >= guard.confirmations;
+        if applied {
+            warn!(
+                deleted,
+                tracked, polls, "Applying deletion exceeding the guard"
+            );
+            self.held_polls = 0;
+        } else {
+            warn!(
+                deleted,
+                tracked, polls, "Holding back deletion exceeding the guard"
+            );
+        }
+
+        self.held_deletion = Some(HeldDeletion {
+            deleted,
+            tracked,
+            polls,
+            applied,
+        });
+        !applied
+    }
+}
+
+/// Backups of older versions only contained the events of the first calendar,
+/// their state is restored under this key until the first calendar is known.
+const LEGACY_CALENDAR_KEY: &str = "";
+
+/// The contents of a backup file
+#[derive(Serialize, Deserialize)]
+struct Backup {
+    calendars: Vec<CalendarBackup>,
+    #[serde(default)]
+    validators: CacheValidators,
+}
+
+/// The state of a single calendar in a backup
+#[derive(Serialize, Deserialize)]
+struct CalendarBackup {
+    key: String,
+    events: HashMap<String, IcalEvent>,
+    /// Older backups derived the kinds of the components from their keys
+    #[serde(default)]
+    kinds: Option<HashMap<String, ComponentKind>>,
+    #[serde(default)]
+    metadata: Option<Vec<Property>>,
+    /// Older backups didn't track components without a UID
+    #[serde(default)]
+    identity: IdentityStrategy,
+    #[serde(default)]
+    pending: HashMap<String, PendingChange>,
+    #[serde(default)]
+    expanded_at: Option<DateTime<Utc>>,
+    /// Consecutive polls a deletion exceeding the [DeletionGuard] was seen on
+    #[serde(default)]
+    held_polls: u32,
+}
+
+/// Identifies a calendar within an ics file by its name (X-WR-CALNAME), or by its position if it is unnamed
+/// or another calendar of the file has the same name
+fn calendar_key(calendar: &IcalCalendar, position: usize, taken: &[String]) -> String {
+    calendar
+        .get_property("X-WR-CALNAME")
+        .and_then(|prop| prop.value.clone())
+        .filter(|name| !name.is_empty() && !taken.contains(name))
+        .unwrap_or_else(|| format!("#{position}"))
 }
 
 pub type CalendarCallback = Box<
@@ -308,25 +851,37 @@ pub type CalendarCallback = Box<
         Option<String>,
         Option<String>,
         Vec<CalendarEvent>,
-    ) -> Pin<
-        Box<dyn Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>> + Send>,
-    >,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send>>,
 >;
 /// Instantiate an [ICSWatcher] using [ICSWatcher::new] to watch for changes of an ics link.
 ///
+/// Every calendar (VCALENDAR) of the ics file is tracked by its own [CalendarChangeDetector],
+/// identified by its name (X-WR-CALNAME) or its position in the file.
+/// The callbacks are called once per changed calendar.
+///
+/// Failed updates are retried according to the [RetryPolicy] (see [ICSWatcher::set_retry_policy]),
+/// errors are reported to the [ErrorHook] (see [ICSWatcher::set_error_hook]).
+///
+/// Links starting with `file://` are read from disk and watched for changes, all other links are fetched over HTTP.
+/// To watch any other [CalendarSource], use [ICSWatcher::from_source].
+///
 /// Using this, you can also [create](`ICSWatcher::create_backup`) and [load](`ICSWatcher::load_backup`) backups.
 /// If you want to handle when the watcher updates, you can manually call the [`ICSWatcher::update`] method.
+/// To watch multiple calendars at once, see [ICSSupervisor].
 ///
 /// # Examples
 ///
-/// ```
+/// ```no_run
+/// # use ics_watcher::{log_events, ICSWatcher};
+/// # #[tokio::main]
+/// # async fn main() {
 /// let mut ics_watcher = ICSWatcher::new(
 ///     "some url",
 ///     vec![
 ///         Box::new(|a, b, e| Box::pin(async move { log_events(a, b, e).await })),
 ///     ],
 /// );
-
+///
 /// // Try to load backup
 /// let _ = ics_watcher.load_backup("Your Calendar");
 /// // Run ics watcher infinitely and save backups as "Your Calendar"
@@ -334,96 +889,502 @@ pub type CalendarCallback = Box<
 ///     .run(Option::from("Your Calendar"))
 ///     .await
 ///     .expect("ICS Watcher crashed");
+/// # }
 /// ```
 pub struct ICSWatcher<'a> {
-    ics_link: &'a str,
+    source: Box<dyn CalendarSource + 'a>,
     pub callbacks: Vec<CalendarCallback>,
-    change_detector: CalendarChangeDetector,
+    change_detectors: Vec<(String, CalendarChangeDetector)>,
+    /// Whether the calendar was polled (or restored from a backup) before, calendars appearing
+    /// afterwards report their events as [`CalendarEvent::Created`]
+    initialized: bool,
+    detector_config: DetectorConfig,
+    ttl: Option<Duration>,
+    retry_policy: RetryPolicy,
+    error_hook: Option<ErrorHook>,
+    deletion_hook: Option<DeletionHook>,
+    backup_dir: PathBuf,
 }
 
 impl<'a> ICSWatcher<'a> {
     pub fn new(ics_link: &'a str, callbacks: Vec<CalendarCallback>) -> Self {
+        match ics_link.strip_prefix("file://") {
+            Some(path) => Self::from_source(FileSource::new(path), callbacks),
+            None => Self::from_source(HttpSource::new(ics_link), callbacks),
+        }
+    }
+
+    pub fn from_source(source: impl CalendarSource + 'a, callbacks: Vec<CalendarCallback>) -> Self {
         ICSWatcher {
-            ics_link,
+            source: Box::new(source),
             callbacks,
-            change_detector: CalendarChangeDetector::new(),
+            change_detectors: Vec::new(),
+            initialized: false,
+            detector_config: DetectorConfig::default(),
+            ttl: None,
+            retry_policy: RetryPolicy::default(),
+            error_hook: None,
+            deletion_hook: None,
+            backup_dir: PathBuf::from(DEFAULT_BACKUP_DIR),
         }
     }
 
-    pub fn restore_state(&mut self, state: HashMap<String, IcalEvent>) {
-        self.change_detector.set_state(state);
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
     }
 
-    pub fn get_state(&self) -> &HashMap<String, IcalEvent> {
-        &self.change_detector.previous
+    /// Reports errors to `error_hook` instead of printing them
+    pub fn set_error_hook(&mut self, error_hook: ErrorHook) {
+        self.error_hook = Some(error_hook);
     }
 
-    pub fn get_calendar_name(&self) -> Option<String> {
-        self.change_detector.name.clone()
+    /// Notifies `deletion_hook` whenever a diff exceeds the [DetectorConfig::deletion_guard]
+    pub fn set_deletion_hook(&mut self, deletion_hook: DeletionHook) {
+        self.deletion_hook = Some(deletion_hook);
     }
 
-    pub fn create_backup(&self, name: &str) {
-        let backup_file_path = Path::new(".backups").join(sanitize(name) + ".cbor");
+    /// Configures the change detection of all calendars, e.g. to [expand recurring events](DetectorConfig::expand_recurrences)
+    pub fn set_detector_config(&mut self, config: DetectorConfig) {
+        for (_, detector) in &mut self.change_detectors {
+            detector.config = DetectorConfig {
+                missing_uid: detector.restored_identity.unwrap_or(config.missing_uid),
+                ..config.clone()
+            };
+        }
+        self.detector_config = config;
+    }
 
-        fs::create_dir_all(".backups").expect("Failed to create .backups folder");
-        let backup_file = File::create(backup_file_path).expect("Failed to create backup file");
-        ciborium::ser::into_writer(self.get_state(), backup_file)
-            .expect("Failed to create and write backup");
+    /// Saves and loads backups in `backup_dir` instead of `.backups` in the working directory
+    pub fn set_backup_dir(&mut self, backup_dir: impl Into<PathBuf>) {
+        self.backup_dir = backup_dir.into();
     }
 
-    pub fn load_backup(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let backup_file_path = File::open(Path::new(".backups").join(sanitize(name) + ".cbor"))?;
+    /// Refreshes every `ttl` instead of using the interval published by the calendar (X-PUBLISHED-TTL)
+    pub fn set_ttl(&mut self, ttl: Option<Duration>) {
+        self.ttl = ttl;
+    }
 
-        let state = ciborium::de::from_reader(backup_file_path)?;
-        self.restore_state(state);
+    /// The interval between two refreshes, the shortest interval any of the calendars asks for
+    pub fn get_ttl(&self) -> Duration {
+        self.ttl.unwrap_or_else(|| {
+            self.change_detectors
+                .iter()
+                .map(|(_, detector)| detector.ttl)
+                .min()
+                .unwrap_or(DEFAULT_TTL)
+        })
+    }
 
-        Ok(())
+    /// The change detectors of all calendars, along with the keys identifying the calendars
+    pub fn detectors(&self) -> impl Iterator<Item = (&str, &CalendarChangeDetector)> {
+        self.change_detectors
+            .iter()
+            .map(|(key, detector)| (key.as_str(), detector))
     }
 
-    pub async fn update(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let res = reqwest::get(self.ics_link).await?;
-        // If server doesn't return 200, return with error
-        if let Err(error) = res.error_for_status_ref() {
-            return Err(error.into());
+    pub fn restore_state(&mut self, key: impl Into<String>, state: HashMap<String, IcalEvent>) {
+        let key = key.into();
+        self.initialized = true;
+        match self.change_detectors.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, detector)) => detector.set_state(state),
+            None => {
+                let mut detector =
+                    CalendarChangeDetector::with_config(self.detector_config.clone());
+                detector.set_state(state);
+                self.change_detectors.push((key, detector));
+            }
         }
-        let res_text = res.text().await?;
-        let buf = BufReader::new(res_text.as_bytes());
-        let calendar = IcalParser::new(buf).next().ok_or("No Calendar present")??;
+    }
 
-        let events = self.change_detector.compare(calendar);
+    pub fn get_state(&self) -> Vec<(&str, &HashMap<String, IcalEvent>)> {
+        self.detectors()
+            .map(|(key, detector)| (key, detector.get_state()))
+            .collect()
+    }
 
-        if !events.is_empty() {
-            let futures: Vec<_> = self
-                .callbacks
+    /// The name of the first calendar
+    pub fn get_calendar_name(&self) -> Option<String> {
+        self.change_detectors
+            .first()
+            .and_then(|(_, detector)| detector.name.clone())
+    }
+
+    pub fn create_backup(&self, name: &str) -> Result<(), Error> {
+        let backup_file_path = self.backup_dir.join(sanitize(name) + ".cbor");
+
+        fs::create_dir_all(&self.backup_dir).map_err(Error::BackupIo)?;
+        let backup_file = File::create(backup_file_path).map_err(Error::BackupIo)?;
+        let backup = Backup {
+            calendars: self
+                .change_detectors
                 .iter()
-                .map(|callback| {
-                    callback(
-                        self.change_detector.name.clone(),
-                        self.change_detector.description.clone(),
-                        events.clone(),
-                    )
+                .map(|(key, detector)| CalendarBackup {
+                    key: key.clone(),
+                    events: detector.get_state().clone(),
+                    kinds: Some(detector.kinds.clone()),
+                    metadata: detector.get_metadata().map(<[Property]>::to_vec),
+                    identity: detector.config.missing_uid,
+                    pending: detector.get_pending().clone(),
+                    expanded_at: detector.expanded_at,
+                    held_polls: detector.held_polls,
                 })
-                .collect();
+                .collect(),
+            validators: self.source.validators(),
+        };
+        ciborium::ser::into_writer(&backup, backup_file)?;
+
+        Ok(())
+    }
+
+    pub fn load_backup(&mut self, name: &str) -> Result<(), Error> {
+        let backup_file =
+            fs::read(self.backup_dir.join(sanitize(name) + ".cbor")).map_err(Error::BackupIo)?;
+
+        let backup = match ciborium::de::from_reader::<Backup, _>(backup_file.as_slice()) {
+            Ok(backup) => backup,
+            // Backups of older versions only contain the events of the first calendar
+            Err(_) => Backup {
+                calendars: vec![CalendarBackup {
+                    key: LEGACY_CALENDAR_KEY.to_string(),
+                    events: ciborium::de::from_reader(backup_file.as_slice())?,
+                    kinds: None,
+                    metadata: None,
+                    identity: IdentityStrategy::Skip,
+                    pending: HashMap::new(),
+                    expanded_at: None,
+                    held_polls: 0,
+                }],
+                validators: CacheValidators::default(),
+            },
+        };
+        for calendar in backup.calendars {
+            // Other strategies would generate other identities for the restored components
+            let restored_identity = (calendar.identity != IdentityStrategy::Skip
+                && calendar
+                    .events
+                    .keys()
+                    .any(|key| identity::is_generated(key)))
+            .then_some(calendar.identity);
+            self.restore_state(calendar.key.clone(), calendar.events);
+            let Some((_, detector)) = self
+                .change_detectors
+                .iter_mut()
+                .find(|(key, _)| *key == calendar.key)
+            else {
+                continue;
+            };
 
-            for future in futures {
-                match future.await {
-                    Ok(()) => (),
-                    Err(err) => eprintln!("Error in callback: {err:?}"),
+            if let Some(kinds) = calendar.kinds {
+                detector.kinds = kinds;
+            }
+            if let Some(metadata) = calendar.metadata {
+                detector.set_metadata(metadata);
+            }
+            detector.set_pending(calendar.pending);
+            detector.expanded_at = calendar.expanded_at;
+            detector.held_polls = calendar.held_polls;
+            detector.restored_identity = restored_identity;
+            if let Some(identity) = restored_identity {
+                if detector.config.missing_uid != identity {
+                    warn!(
+                        calendar = calendar.key,
+                        configured = ?detector.config.missing_uid,
+                        backup = ?identity,
+                        "Keeping the identity strategy of the backup"
+                    );
+                    detector.config.missing_uid = identity;
                 }
             }
         }
+        self.source.set_validators(backup.validators);
 
         Ok(())
     }
 
-    pub async fn run(&mut self, backup: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    #[instrument(name = "poll", skip_all, fields(feed = %self.feed()))]
+    pub async fn update(&mut self) -> Result<(), Error> {
+        let result = self.poll().await;
+
+        #[cfg(feature = "metrics")]
+        metrics::record_poll(
+            &self.feed(),
+            match &result {
+                Ok(true) => "fetched",
+                Ok(false) => "not_modified",
+                Err(_) => "error",
+            },
+        );
+
+        result.map(|_| ())
+    }
+
+    /// Fetches the calendar and notifies the callbacks of any changes.
+    ///
+    /// The [CacheValidators] of the fetched version are only committed once all callbacks succeeded
+    /// and no deletion is held back by the [DeletionGuard], otherwise the version is fetched and
+    /// compared again on the next poll. Calendars whose callbacks failed are reset to their previous
+    /// state, so their changes are delivered again.
+    ///
+    /// Returns whether the calendar was fetched, or the source reported it to be unchanged.
+    async fn poll(&mut self) -> Result<bool, Error> {
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+
+        let fetched = self.source.fetch().await;
+        #[cfg(feature = "metrics")]
+        metrics::record_fetch(
+            &self.feed(),
+            match &fetched {
+                Ok(Some(_)) => "fetched",
+                Ok(None) => "not_modified",
+                Err(_) => "error",
+            },
+            started.elapsed(),
+        );
+
+        let Some(FetchedCalendar {
+            contents: res_text,
+            validators,
+        }) = fetched?
+        else {
+            debug!("Calendar didn't change since the last fetch");
+            return Ok(false);
+        };
+
+        #[cfg(feature = "metrics")]
+        metrics::record_fetched_bytes(&self.feed(), res_text.len());
+
+        let buf = BufReader::new(res_text.as_bytes());
+        let calendars = IcalParser::new(buf).collect::<Result<Vec<_>, _>>()?;
+        if calendars.is_empty() {
+            return Err(Error::NoCalendar);
+        }
+        debug!(
+            bytes = res_text.len(),
+            calendars = calendars.len(),
+            "Fetched calendar"
+        );
+
+        let mut keys: Vec<String> = Vec::with_capacity(calendars.len());
+        for (position, calendar) in calendars.iter().enumerate() {
+            let key = calendar_key(calendar, position, &keys);
+            keys.push(key);
+        }
+
+        let mut previous_detectors = std::mem::take(&mut self.change_detectors);
+        // A calendar which got a new name keeps its detector, so the rename is reported as MetadataChanged
+        for (position, key) in keys.iter().enumerate() {
+            let renamed = previous_detectors
+                .get(position)
+                .is_some_and(|(previous, _)| {
+                    !keys.contains(previous) && !previous_detectors.iter().any(|(k, _)| k == key)
+                });
+            if renamed {
+                debug!(
+                    from = previous_detectors[position].0,
+                    to = key,
+                    "Calendar was renamed"
+                );
+                previous_detectors[position].0 = key.clone();
+            }
+        }
+        let mut changes = Vec::with_capacity(calendars.len());
+
+        for (position, (calendar, key)) in calendars.into_iter().zip(keys).enumerate() {
+            let known = previous_detectors
+                .iter()
+                .position(|(k, _)| *k == key)
+                // Backups of older versions only contain the first calendar
+                .or_else(|| match position {
+                    0 => previous_detectors
+                        .iter()
+                        .position(|(k, _)| k == LEGACY_CALENDAR_KEY),
+                    _ => None,
+                });
+            let (snapshot, mut detector) = match known {
+                Some(index) => {
+                    let detector = previous_detectors.remove(index).1;
+                    (Some(detector.clone()), detector)
+                }
+                None => {
+                    let mut detector =
+                        CalendarChangeDetector::with_config(self.detector_config.clone());
+                    // A calendar added to the file later on is new, not part of the setup
+                    if self.initialized {
+                        detector.set_state(HashMap::new());
+                    }
+                    (None, detector)
+                }
+            };
+
+            let events = detector.compare(calendar);
+            self.report_held_deletion(&detector);
+            changes.push((
+                key.clone(),
+                snapshot,
+                detector.name.clone(),
+                detector.description.clone(),
+                events,
+            ));
+            self.change_detectors.push((key, detector));
+        }
+
+        // Calendars which aren't part of the file anymore
+        for (key, mut detector) in previous_detectors {
+            let snapshot = detector.clone();
+            let events = detector.clear();
+            self.report_held_deletion(&detector);
+            changes.push((
+                key.clone(),
+                Some(snapshot),
+                detector.name.clone(),
+                detector.description.clone(),
+                events,
+            ));
+            if detector.held_deletion().is_some_and(|held| !held.applied) {
+                self.change_detectors.push((key, detector));
+            }
+        }
+
+        let mut notified = true;
+        for (key, snapshot, name, description, events) in changes {
+            #[cfg(feature = "metrics")]
+            metrics::record_changes(&self.feed(), &events);
+
+            if !self.notify(name, description, events).await {
+                // Detected again on the next poll, as the version is fetched again
+                self.restore_detector(key, snapshot);
+                notified = false;
+            }
+        }
+        // A held deletion is only confirmed by seeing it again, not by the source reporting no changes
+        let held = self
+            .change_detectors
+            .iter()
+            .any(|(_, detector)| detector.held_deletion().is_some_and(|held| !held.applied));
+        if notified {
+            self.initialized = true;
+            if !held {
+                self.source.set_validators(validators);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// The location of the source, safe to log
+    fn feed(&self) -> String {
+        source::redact(self.source.location())
+    }
+
+    /// Resets the detector of the calendar `key` to `snapshot`, removes it if the calendar was new
+    fn restore_detector(&mut self, key: String, snapshot: Option<CalendarChangeDetector>) {
+        let index = self.change_detectors.iter().position(|(k, _)| *k == key);
+        match (index, snapshot) {
+            (Some(index), Some(snapshot)) => self.change_detectors[index].1 = snapshot,
+            (Some(index), None) => {
+                self.change_detectors.remove(index);
+            }
+            (None, Some(snapshot)) => self.change_detectors.push((key, snapshot)),
+            (None, None) => {}
+        }
+    }
+
+    fn report_held_deletion(&self, detector: &CalendarChangeDetector) {
+        if let (Some(hook), Some(held)) = (&self.deletion_hook, detector.held_deletion()) {
+            hook(detector.name.as_deref(), held);
+        }
+    }
+
+    /// Passes `events` to all callbacks, returns whether all of them succeeded
+    async fn notify(
+        &self,
+        name: Option<String>,
+        description: Option<String>,
+        events: Vec<CalendarEvent>,
+    ) -> bool {
+        if events.is_empty() {
+            return true;
+        }
+
+        let futures: Vec<_> = self
+            .callbacks
+            .iter()
+            .map(|callback| callback(name.clone(), description.clone(), events.clone()))
+            .collect();
+
+        let mut succeeded = true;
+        for (index, future) in futures.into_iter().enumerate() {
+            let span = info_span!(
+                "callback",
+                index,
+                calendar = name.as_deref(),
+                changes = events.len()
+            );
+            match future.instrument(span.clone()).await {
+                Ok(()) => (),
+                Err(err) => {
+                    succeeded = false;
+                    span.in_scope(|| warn!(error = %err, "Error in callback"));
+                    #[cfg(feature = "metrics")]
+                    metrics::record_callback_failure(&self.feed());
+                }
+            }
+        }
+        succeeded
+    }
+
+    /// Calls [ICSWatcher::update] until it succeeds or the [RetryPolicy] gives up.
+    ///
+    /// Returns whether the update succeeded.
+    pub async fn update_with_retries(&mut self) -> bool {
+        let mut attempt = 0;
         loop {
-            self.update().await?;
+            attempt += 1;
+            let error = match self.update().await {
+                Ok(()) => return true,
+                Err(error) => error,
+            };
+
+            let transient = error.is_transient();
+            let status = RetryStatus {
+                attempt,
+                transient,
+                retry_in: (transient && attempt < self.retry_policy.max_attempts)
+                    .then(|| self.retry_policy.backoff(attempt)),
+            };
+            match &self.error_hook {
+                Some(hook) => hook(&error, &status),
+                None => warn!(
+                    feed = %self.feed(),
+                    attempt,
+                    transient,
+                    retry_in = ?status.retry_in,
+                    %error,
+                    "Updating calendar failed"
+                ),
+            }
+
+            match status.retry_in {
+                Some(backoff) => sleep(backoff).await,
+                None => return false,
+            }
+        }
+    }
+
+    /// Updates the calendar forever, saving a backup named `backup` after every update.
+    ///
+    /// Neither failed updates (see [ICSWatcher::update_with_retries]) nor failing to save the backup stop
+    /// the watcher, the backup is saved again after the next update.
+    pub async fn run(&mut self, backup: Option<&str>) -> Result<(), Error> {
+        loop {
+            self.update_with_retries().await;
             if let Some(path) = backup {
-                self.create_backup(path);
+                if let Err(error) = self.create_backup(path) {
+                    warn!(backup = path, %error, "Saving backup failed");
+                }
             }
-            println!("Refreshing in {:?}", self.change_detector.ttl);
-            sleep(self.change_detector.ttl).await;
+            self.source.wait(self.get_ttl()).await;
         }
     }
 }
@@ -434,7 +1395,10 @@ impl<'a> ICSWatcher<'a> {
 ///
 /// # Examples
 ///
-/// ```
+/// ```no_run
+/// # use ics_watcher::{log_events, ICSWatcher};
+/// # #[tokio::main]
+/// # async fn main() {
 /// let mut ics_watcher = ICSWatcher::new(
 ///     "some url",
 ///     vec![
@@ -448,36 +1412,86 @@ impl<'a> ICSWatcher<'a> {
 ///     .run(Option::from("Your Calendar"))
 ///     .await
 ///     .expect("ICS Watcher crashed");
+/// # }
 /// ```
 pub async fn log_events(
     name: Option<String>,
     description: Option<String>,
     events: Vec<CalendarEvent>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    println!(
-        "Captured changes of {}{}:",
-        name.as_deref().unwrap_or("Unnamed Calendar"),
-        description
-            .as_deref()
-            .and_then(|desc| Some(format!(" ({desc})")))
-            .unwrap_or(String::from(""))
+) -> Result<(), Error> {
+    let span = info_span!(
+        "log_events",
+        calendar = name.as_deref().unwrap_or("Unnamed Calendar"),
+        description = description.as_deref()
     );
+    let _span = span.enter();
+
+    info!(changes = events.len(), "Captured changes");
     for event in events {
         match event {
-            CalendarEvent::Setup(EventData { uid, ical_data }) => {
-                println!("Setup {uid}: {ical_data:?}\n")
+            CalendarEvent::Setup(EventData {
+                uid,
+                kind,
+                ical_data,
+                ..
+            }) => {
+                info!(uid, kind = kind.name(), event = ?ical_data, "Setup")
             }
-            CalendarEvent::Created(EventData { uid, ical_data }) => {
-                println!("Created {uid}: {ical_data:?}\n")
+            CalendarEvent::Created(EventData {
+                uid,
+                kind,
+                ical_data,
+                ..
+            }) => {
+                info!(uid, kind = kind.name(), event = ?ical_data, "Created")
             }
             CalendarEvent::Updated {
                 event,
                 changed_properties,
+                changed_alarms,
+            } => {
+                info!(
+                    uid = event.uid,
+                    kind = event.kind.name(),
+                    ?changed_properties,
+                    ?changed_alarms,
+                    "Updated"
+                )
+            }
+            CalendarEvent::Deleted(EventData {
+                uid,
+                kind,
+                ical_data,
+                ..
+            }) => {
+                info!(uid, kind = kind.name(), event = ?ical_data, "Deleted")
+            }
+            CalendarEvent::Regressed {
+                event,
+                changed_properties,
+            } => {
+                warn!(
+                    uid = event.uid,
+                    kind = event.kind.name(),
+                    ?changed_properties,
+                    "Ignoring stale version"
+                )
+            }
+            CalendarEvent::Moved {
+                from,
+                to,
+                changed_properties,
             } => {
-                println!("Updated {}: {:?}\n", event.uid, changed_properties)
+                info!(
+                    from = from.uid,
+                    to = to.uid,
+                    kind = to.kind.name(),
+                    ?changed_properties,
+                    "Moved"
+                )
             }
-            CalendarEvent::Deleted(EventData { uid, ical_data }) => {
-                println!("Deleted {uid}: {ical_data:?}\n")
+            CalendarEvent::MetadataChanged(changed_properties) => {
+                info!(?changed_properties, "Calendar changed")
             }
         }
     }
@@ -542,76 +1556,102 @@ fn convert_to_non_digits(str: String) -> String {
         .collect::<String>()
 }
 
+/// Counts failed Google Calendar API calls of `operation`
+fn google_api_error(operation: &'static str) -> impl FnOnce(&google_calendar3::Error) {
+    move |_| {
+        #[cfg(feature = "metrics")]
+        metrics::record_google_api_error(operation);
+        #[cfg(not(feature = "metrics"))]
+        let _ = operation;
+    }
+}
+
+/// The lectures of TUM take place in Munich, dates and floating times are interpreted there
+const TUM_TIMEZONE: Tz = chrono_tz::Europe::Berlin;
+
+/// Converts the start and end (DTEND or DURATION) of `event` for the Google Calendar API.
+///
+/// Events with dates instead of times become all-day events, spanning from DTSTART
+/// up to (excluding) DTEND like in iCalendar.
+fn google_times(
+    event: &IcalEvent,
+    timezones: &Timezones,
+) -> Result<(EventDateTime, EventDateTime), Box<dyn std::error::Error + Send + Sync>> {
+    let start = datetime::event_start(event, timezones)?;
+    let end = match datetime::event_end(event, timezones)? {
+        Some(end) => end,
+        // Without an end, all-day events last one day and other events take no time
+        None => match start {
+            CalendarTime::Date(date) => {
+                CalendarTime::Date(date.succ_opt().ok_or("DTSTART out of range")?)
+            }
+            time => time,
+        },
+    };
+
+    let timed = |time: CalendarTime| EventDateTime {
+        date_time: Some(time.to_utc(TUM_TIMEZONE)),
+        date: None,
+        time_zone: None,
+    };
+    let all_day = |date| EventDateTime {
+        date_time: None,
+        date: Some(date),
+        time_zone: None,
+    };
+
+    match (start, end) {
+        (CalendarTime::Date(start), CalendarTime::Date(end)) => {
+            // Google rejects all-day events ending on the day they start
+            let end = end.max(start.succ_opt().ok_or("DTSTART out of range")?);
+            Ok((all_day(start), all_day(end)))
+        }
+        (CalendarTime::Date(_), _) | (_, CalendarTime::Date(_)) => {
+            Err("DTSTART and DTEND have to be both dates or both times".into())
+        }
+        (start, end) => Ok((timed(start), timed(end))),
+    }
+}
+
 // TODO: Refactor create and update event
+#[instrument(skip(hub, event), fields(uid = %uid))]
 async fn create_event(
     hub: &CalendarHub<HttpsConnector<HttpConnector>>,
     uid: String,
     event: IcalEvent,
+    timezones: &Timezones,
     calendar_id: &str,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut google_event = Event::default();
-
-    let start = NaiveDateTime::parse_from_str(
-        &event
-            .get_property("DTSTART")
-            .ok_or("Required property DTSTART missing")?
-            .value
-            .clone()
-            .ok_or("Required property value DTSTART missing")?[0..15],
-        "%Y%m%dT%H%M%S",
-    )?
-    .and_utc();
-    let end = NaiveDateTime::parse_from_str(
-        &event
-            .get_property("DTEND")
-            .ok_or("Required property DTEND missing")?
-            .value
-            .clone()
-            .ok_or("Required property value DTEND missing")?[0..15],
-        "%Y%m%dT%H%M%S",
-    )?
-    .and_utc();
-
-    google_event.start = Some(EventDateTime {
-        date_time: Some(start),
-        date: None,
-        time_zone: None,
-    });
-    google_event.end = Some(EventDateTime {
-        date_time: Some(end),
-        date: None,
-        time_zone: None,
-    });
+    let (start, end) = google_times(&event, timezones)?;
+    let mut google_event = GoogleEvent {
+        start: Some(start),
+        end: Some(end),
+        ..Default::default()
+    };
+    let event = Event::without_times(&event);
 
     let room = event
-        .get_property("LOCATION")
-        .and_then(|loc| loc.value.clone())
-        .map(|s| s.replace(r"\", ""))
+        .location
+        .clone()
         .unwrap_or_else(|| "Kein Ort angegeben".to_string());
 
     let i_cal_uid = convert_to_non_digits(uid.replace("@tum.de", "|").to_string());
 
     // google_event.reminders would be useful for exams
-    if let Some(url) = event.get_property("URL").and_then(|url| url.value.clone()) {
+    if let Some(url) = event.url.clone() {
         google_event.source = Some(google_calendar3::api::EventSource {
             title: Some("Link zur Lernveranstaltung".to_string()),
             url: Some(url),
         });
     }
 
-    if let Some(status) = event
-        .get_property("STATUS")
-        .and_then(|status| status.value.clone())
-    {
-        google_event.status = Some(status.to_lowercase());
+    if let Some(status) = &event.status {
+        google_event.status = Some(status.name().to_lowercase());
     }
 
-    match event
-        .get_property("SUMMARY")
-        .and_then(|summary| summary.value.clone())
-    {
+    match &event.summary {
         Some(summary) => {
-            google_event.summary = Some(replace_courses(summary.replace(r"\", "").as_str()));
+            google_event.summary = Some(replace_courses(summary));
             if summary.contains("Prüfung") {
                 // Big important :o
                 google_event.color_id = Some(String::from("11"));
@@ -625,13 +1665,12 @@ async fn create_event(
     google_event.location = Some(convert_to_non_digits(room.clone()));
 
     let link = format!("https://nav.tum.de/search?q={}", room.clone());
+    // TUM descriptions start with the status and type of the event, e.g. `fix; Vorlesung; ...`
     let description = event
-        .get_property("DESCRIPTION")
-        .and_then(|prop| prop.value.clone())
-        .map(|desc| desc.split(r"\;").skip(2).collect())
-        .unwrap_or(String::new())
-        .as_str()
-        .replace(r"\", "")
+        .description
+        .as_deref()
+        .map(|desc| desc.split(';').skip(2).collect::<String>())
+        .unwrap_or_default()
         .trim()
         .to_string();
 
@@ -665,7 +1704,8 @@ async fn create_event(
         .list(calendar_id)
         .q(&format!("uid:{}", i_cal_uid))
         .doit()
-        .await?;
+        .await
+        .inspect_err(google_api_error("list"))?;
 
     if let Some(event_id) = results
         .1
@@ -676,27 +1716,31 @@ async fn create_event(
         hub.events()
             .update(google_event, calendar_id, &event_id)
             .doit()
-            .await?
+            .await
+            .inspect_err(google_api_error("update"))?
             .0
     } else {
         hub.events()
             .insert(google_event, calendar_id)
             .doit()
-            .await?
+            .await
+            .inspect_err(google_api_error("insert"))?
             .0
     };
 
     Ok(())
 }
 
+#[instrument(skip(hub, event, property_changes), fields(uid = %uid))]
 async fn update_event(
     hub: &CalendarHub<HttpsConnector<HttpConnector>>,
     uid: String,
     event: IcalEvent,
+    timezones: &Timezones,
     property_changes: Vec<PropertyChange>,
     calendar_id: &str,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    println!("Updating event {uid}: {property_changes:?}");
+    info!(?property_changes, "Updating event");
 
     let i_cal_uid = convert_to_non_digits(uid.replace("@tum.de", "|").to_string());
     let results = hub
@@ -704,7 +1748,8 @@ async fn update_event(
         .list(calendar_id)
         .q(&format!("uid:{}", i_cal_uid))
         .doit()
-        .await?;
+        .await
+        .inspect_err(google_api_error("list"))?;
 
     let oringinal_event = results
         .1
@@ -714,50 +1759,26 @@ async fn update_event(
     let event_id = oringinal_event
         .id
         .ok_or("Google didn't provide the event with an ID")?;
-    let mut google_event = Event::default();
+    let mut google_event = GoogleEvent::default();
 
-    if property_changes
-        .iter()
-        .any(|property_change| property_change.key == "DTSTART" || property_change.key == "DTEND")
-    {
-        let start = NaiveDateTime::parse_from_str(
-            &event
-                .get_property("DTSTART")
-                .ok_or("Required property DTSTART missing")?
-                .value
-                .clone()
-                .ok_or("Required property value DTSTART missing")?[0..15],
-            "%Y%m%dT%H%M%S",
-        )?
-        .and_utc();
-        let end = NaiveDateTime::parse_from_str(
-            &event
-                .get_property("DTEND")
-                .ok_or("Required property DTEND missing")?
-                .value
-                .clone()
-                .ok_or("Required property DTEND value missing")?[0..15],
-            "%Y%m%dT%H%M%S",
-        )?
-        .and_utc();
-
-        google_event.start = Some(EventDateTime {
-            date_time: Some(start),
-            date: None,
-            time_zone: None,
-        });
-        google_event.end = Some(EventDateTime {
-            date_time: Some(end),
-            date: None,
-            time_zone: None,
-        });
+    if property_changes.iter().any(|property_change| {
+        matches!(
+            property_change.key.as_str(),
+            "DTSTART" | "DTEND" | "DURATION"
+        )
+    }) {
+        // Switching between timed and all-day replaces both fields, as the event is updated as a whole
+        let (start, end) = google_times(&event, timezones)?;
+        google_event.start = Some(start);
+        google_event.end = Some(end);
     } else {
         google_event.start = oringinal_event.start;
         google_event.end = oringinal_event.end;
     }
+    let event = Event::without_times(&event);
 
     // google_event.reminders would be useful for exams
-    if let Some(url) = event.get_property("URL").and_then(|url| url.value.clone()) {
+    if let Some(url) = event.url.clone() {
         google_event.source = Some(google_calendar3::api::EventSource {
             title: Some("Link zur Lernveranstaltung".to_string()),
             url: Some(url),
@@ -768,11 +1789,8 @@ async fn update_event(
         .iter()
         .any(|property_change| property_change.key == "STATUS")
     {
-        if let Some(status) = event
-            .get_property("STATUS")
-            .and_then(|status| status.value.clone())
-        {
-            google_event.status = Some(status.to_lowercase());
+        if let Some(status) = &event.status {
+            google_event.status = Some(status.name().to_lowercase());
         }
     } else {
         google_event.status = oringinal_event.status;
@@ -782,12 +1800,9 @@ async fn update_event(
         .iter()
         .any(|property_change| property_change.key == "SUMMARY")
     {
-        match event
-            .get_property("SUMMARY")
-            .and_then(|summary| summary.value.clone())
-        {
+        match &event.summary {
             Some(summary) => {
-                google_event.summary = Some(replace_courses(summary.replace(r"\", "").as_str()));
+                google_event.summary = Some(replace_courses(summary));
                 if summary.contains("Prüfung") {
                     // 11 = Tomato (Google Calendar's Red)
                     google_event.color_id = Some(String::from("11"));
@@ -811,9 +1826,8 @@ async fn update_event(
 
     // If room has changed, update all properties associated with the room
     let room = event
-        .get_property("LOCATION")
-        .and_then(|loc| loc.value.clone())
-        .map(|s| s.replace(r"\", ""))
+        .location
+        .clone()
         .unwrap_or_else(|| "Kein Ort angegeben".to_string());
     if property_changes
         .iter()
@@ -830,12 +1844,10 @@ async fn update_event(
     );
 
     let description = event
-        .get_property("DESCRIPTION")
-        .and_then(|prop| prop.value.clone())
-        .map(|desc| desc.split(r"\;").skip(2).collect())
-        .unwrap_or(String::new())
-        .as_str()
-        .replace(r"\", "")
+        .description
+        .as_deref()
+        .map(|desc| desc.split(';').skip(2).collect::<String>())
+        .unwrap_or_default()
         .trim()
         .to_string();
 
@@ -882,12 +1894,74 @@ async fn update_event(
     hub.events()
         .update(google_event, calendar_id, &event_id)
         .doit()
-        .await?
-        .0;
+        .await
+        .inspect_err(google_api_error("update"))?;
+
+    Ok(())
+}
+
+/// Points the Google event of `from` to the new UID `to`, keeping the edits made in Google Calendar
+#[instrument(skip(hub, event, property_changes))]
+async fn move_event(
+    hub: &CalendarHub<HttpsConnector<HttpConnector>>,
+    from: String,
+    to: String,
+    event: IcalEvent,
+    timezones: &Timezones,
+    property_changes: Vec<PropertyChange>,
+    calendar_id: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let from_uid = convert_to_non_digits(from.replace("@tum.de", "|").to_string());
+    let to_uid = convert_to_non_digits(to.replace("@tum.de", "|").to_string());
+    let results = hub
+        .events()
+        .list(calendar_id)
+        .q(&format!("uid:{}", from_uid))
+        .doit()
+        .await
+        .inspect_err(google_api_error("list"))?;
+
+    // Deleted in Google Calendar, so it shouldn't come back
+    let Some(event_id) = results
+        .1
+        .items
+        .and_then(|items| items.first().cloned())
+        .and_then(|event| event.id)
+    else {
+        return Ok(());
+    };
+
+    if !property_changes.is_empty() {
+        update_event(hub, from, event, timezones, property_changes, calendar_id).await?;
+    }
+
+    let (_, google_event) = hub
+        .events()
+        .get(calendar_id, &event_id)
+        .doit()
+        .await
+        .inspect_err(google_api_error("get"))?;
+    let description = google_event
+        .description
+        .unwrap_or_default()
+        .replace(&format!("uid:{}", from_uid), &format!("uid:{}", to_uid));
+    hub.events()
+        .patch(
+            GoogleEvent {
+                description: Some(description),
+                ..Default::default()
+            },
+            calendar_id,
+            &event_id,
+        )
+        .doit()
+        .await
+        .inspect_err(google_api_error("patch"))?;
 
     Ok(())
 }
 
+#[instrument(skip(hub), fields(uid = %uid))]
 async fn delete_event(
     hub: &CalendarHub<HttpsConnector<HttpConnector>>,
     uid: String,
@@ -899,7 +1973,8 @@ async fn delete_event(
         .list(calendar_id)
         .q(&format!("uid:{}", i_cal_uid))
         .doit()
-        .await?;
+        .await
+        .inspect_err(google_api_error("list"))?;
 
     if let Some(event_id) = results
         .1
@@ -907,12 +1982,24 @@ async fn delete_event(
         .and_then(|items| items.first().cloned())
         .and_then(|event| event.id)
     {
-        hub.events().delete(calendar_id, &event_id).doit().await?;
+        hub.events()
+            .delete(calendar_id, &event_id)
+            .doit()
+            .await
+            .inspect_err(google_api_error("delete"))?;
     }
 
     Ok(())
 }
 
+/// Whether the event is a video transmission of a lecture in another room, which isn't synced
+fn is_video_transmission(event: &IcalEvent) -> bool {
+    event
+        .get_property("DESCRIPTION")
+        .and_then(|prop| prop.value.as_deref())
+        .is_some_and(|desc| desc.contains("Videoübertragung aus"))
+}
+
 /// This is a callback which synchronizes your TUM Calendar to your Google Calender.
 ///
 /// The event summaries will be shortened and the events themselves modifieable. As soon as you delete an event, it won't come back.
@@ -920,7 +2007,12 @@ async fn delete_event(
 ///
 /// # Examples
 ///
-/// ```
+/// ```no_run
+/// # use ics_watcher::{tum_google_sync, ICSWatcher};
+/// # #[tokio::main]
+/// # async fn main() {
+/// # let tum_url = String::new();
+/// # let google_calendar_id = String::new();
 /// let mut ics_watcher = ICSWatcher::new(
 ///     tum_url.as_str(),
 ///     vec![
@@ -930,24 +2022,26 @@ async fn delete_event(
 ///         }),
 ///     ],
 /// );
-
+///
 /// // Try to load backup
 /// let _ = ics_watcher.load_backup("TUM Calendar");
 /// ics_watcher
 ///     .run(Option::from("TUM Calendar"))
 ///     .await
 ///     .expect("ICS Watcher crashed");
+/// # }
 /// ```
+#[instrument(skip_all, fields(calendar_id = %calendar_id, changes = events.len()))]
 pub async fn tum_google_sync(
     calendar_id: &str,
     _: Option<String>,
     _: Option<String>,
     events: Vec<CalendarEvent>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+) -> Result<(), Error> {
     let secret: yup_oauth2::ApplicationSecret =
         read_application_secret(Path::new(".secrets/client_secret.json"))
             .await
-            .expect("Failed to read client secret");
+            .map_err(Error::callback)?;
 
     let auth = yup_oauth2::InstalledFlowAuthenticator::builder(
         secret,
@@ -955,16 +2049,18 @@ pub async fn tum_google_sync(
     )
     .persist_tokens_to_disk(".secrets/token_cache.json")
     .build()
-    .await?;
+    .await
+    .map_err(Error::callback)?;
 
     auth.token(&["https://www.googleapis.com/auth/calendar"])
         .await
-        .expect("Unable to get scope for calendar");
+        .map_err(Error::callback)?;
 
     let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
         .build(
             hyper_rustls::HttpsConnectorBuilder::new()
-                .with_native_roots()?
+                .with_native_roots()
+                .map_err(Error::callback)?
                 .https_or_http()
                 .enable_http1()
                 .build(),
@@ -972,49 +2068,65 @@ pub async fn tum_google_sync(
     let hub = CalendarHub::new(client, auth);
 
     for event in events {
-        let calendar_id = calendar_id;
+        // Tasks and other components can't be synced as Google Calendar events
+        if event
+            .data()
+            .is_some_and(|data| data.kind != ComponentKind::Event)
+        {
+            continue;
+        }
 
         let result = match event {
-            CalendarEvent::Setup(EventData { uid, ical_data }) => {
+            CalendarEvent::Setup(EventData {
+                uid,
+                ical_data,
+                timezones,
+                ..
+            }) => {
                 // Don't sync if event is a video transmission
-                if ical_data
-                    .get_property("DESCRIPTION")
-                    .and_then(|prop| prop.value.clone())
-                    .map(|desc| desc.contains("Videoübertragung aus"))
-                    .unwrap_or(false)
-                {
+                if is_video_transmission(&ical_data) {
                     Err(format!(
                         "Skipping video transmission event {:?}",
                         ical_data.get_property("SUMMARY"),
                     )
                     .into())
                 } else {
-                    println!("Setting up event {uid}");
-                    create_event(&hub, uid, ical_data, calendar_id).await
+                    info!(uid, "Setting up event");
+                    create_event(&hub, uid, ical_data, &timezones, calendar_id).await
                 }
             }
-            CalendarEvent::Created(EventData { uid, ical_data }) => {
+            CalendarEvent::Created(EventData {
+                uid,
+                ical_data,
+                timezones,
+                ..
+            }) => {
                 // Don't sync if event is a video transmission
-                if ical_data
-                    .get_property("DESCRIPTION")
-                    .and_then(|prop| prop.value.clone())
-                    .map(|desc| desc.contains("Videoübertragung aus"))
-                    .unwrap_or(false)
-                {
+                if is_video_transmission(&ical_data) {
                     // Skipping video transmission event
                     Ok(())
                 } else {
-                    println!("Creating event {uid}");
-                    create_event(&hub, uid, ical_data, calendar_id).await
+                    info!(uid, "Creating event");
+                    create_event(&hub, uid, ical_data, &timezones, calendar_id).await
                 }
             }
             CalendarEvent::Updated {
-                event: EventData { uid, ical_data },
+                event:
+                    EventData {
+                        uid,
+                        ical_data,
+                        timezones,
+                        ..
+                    },
                 changed_properties,
+                ..
             } => {
+                // Reminders aren't synced, so events where only they changed are skipped.
                 // The TUM Calendar seems to randomly serve english / german descriptions
                 // This looks for differences other than the first two words in english / german
-                if changed_properties.len() == 1
+                if changed_properties.is_empty() {
+                    Ok(())
+                } else if changed_properties.len() == 1
                     && changed_properties[0].key == "DESCRIPTION"
                     && changed_properties[0]
                         .from
@@ -1026,33 +2138,44 @@ pub async fn tum_google_sync(
                                 .as_ref()
                                 .and_then(|to| to.value.as_ref()),
                         )
-                        .map_or(false, |(from, to)| {
-                            from.split(";").skip(2).collect::<String>()
-                                == to.split(";").skip(2).collect::<String>()
+                        .is_some_and(|(from, to)| {
+                            // Compares the text as synced, where `\;` is a plain semicolon
+                            let rest = |description: &str| {
+                                diff::unescape_text(description)
+                                    .split(';')
+                                    .skip(2)
+                                    .collect::<String>()
+                            };
+                            rest(from) == rest(to)
                         })
                 {
                     // Update is a language-only update
                     Ok(())
                 } else {
-                    update_event(&hub, uid, ical_data, changed_properties, calendar_id).await
+                    update_event(
+                        &hub,
+                        uid,
+                        ical_data,
+                        &timezones,
+                        changed_properties,
+                        calendar_id,
+                    )
+                    .await
                 }
             }
-            CalendarEvent::Deleted(EventData { uid, ical_data }) => {
-                println!("Deleting event {uid}");
+            CalendarEvent::Deleted(EventData {
+                uid,
+                ical_data,
+                timezones,
+                ..
+            }) => {
+                info!(uid, "Deleting event");
                 // If the event is in the far past, we assume it's just the calendar updating
                 // for the next semester, which means we don't actually need to delete it
-                let end_date = ical_data
-                    .get_property("DTEND")
-                    .and_then(|prop| prop.value.clone())
-                    .and_then(|value| {
-                        if value.len() >= 15 {
-                            NaiveDateTime::parse_from_str(&value[0..15], "%Y%m%dT%H%M%S")
-                                .map(|dt| dt.and_utc())
-                                .ok()
-                        } else {
-                            None
-                        }
-                    });
+                let end_date = datetime::event_end(&ical_data, &timezones)
+                    .ok()
+                    .flatten()
+                    .map(|end| end.to_utc(TUM_TIMEZONE));
 
                 match end_date {
                     Some(end) if end < Utc::now() - Duration::from_secs(60 * 24 * 7) => {
@@ -1062,11 +2185,54 @@ pub async fn tum_google_sync(
                     _ => delete_event(&hub, uid, calendar_id).await,
                 }
             }
+            CalendarEvent::Moved {
+                from,
+                to,
+                changed_properties,
+            } => {
+                // Video transmissions aren't synced, so only the other version can be in Google Calendar
+                match (
+                    is_video_transmission(&from.ical_data),
+                    is_video_transmission(&to.ical_data),
+                ) {
+                    (true, true) => Ok(()),
+                    (false, true) => {
+                        info!(
+                            uid = from.uid,
+                            "Deleting event moved to a video transmission"
+                        );
+                        delete_event(&hub, from.uid, calendar_id).await
+                    }
+                    (true, false) => {
+                        info!(
+                            uid = to.uid,
+                            "Creating event moved from a video transmission"
+                        );
+                        create_event(&hub, to.uid, to.ical_data, &to.timezones, calendar_id).await
+                    }
+                    (false, false) => {
+                        move_event(
+                            &hub,
+                            from.uid,
+                            to.uid,
+                            to.ical_data,
+                            &to.timezones,
+                            changed_properties,
+                            calendar_id,
+                        )
+                        .await
+                    }
+                }
+            }
+            // Stale versions would revert the Google event
+            CalendarEvent::Regressed { .. } => Ok(()),
+            // The Google calendar keeps its own name
+            CalendarEvent::MetadataChanged(_) => Ok(()),
         };
 
         match result {
             Ok(_) => (),
-            Err(error) => eprintln!("Error on syncing event: {error:?}"),
+            Err(error) => warn!(%error, "Error on syncing event"),
         }
     }
 
@@ -1076,337 +2242,542 @@ pub async fn tum_google_sync(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{self, calendar};
 
-    #[test]
-    fn cmp_no_properties() {
-        let event1 = IcalEvent {
-            properties: vec![],
-            alarms: vec![],
-        };
-
-        let event2 = IcalEvent {
-            properties: vec![],
-            alarms: vec![],
-        };
-
-        let keys = changed_properties(&event1, &event2);
-
-        assert_eq!(keys, None);
+    fn timed_event(dtstart: &str, dtend: Option<&str>) -> IcalEvent {
+        testing::event(&format!(
+            "UID:1\n{dtstart}\n{}",
+            dtend.map(|dtend| format!("{dtend}\n")).unwrap_or_default()
+        ))
     }
 
     #[test]
-    fn cmp_same_properties() {
-        let prop1 = Property {
-            name: String::from("prop1"),
-            value: Some(String::from("prop1 value")),
-            params: None,
-        };
-
-        let prop2 = Property {
-            name: String::from("prop2"),
-            value: Some(String::from("prop2 value")),
-            params: None,
-        };
-
-        let event1 = IcalEvent {
-            properties: vec![prop1.clone(), prop2.clone()],
-            alarms: vec![],
-        };
-
-        let event2 = IcalEvent {
-            properties: vec![prop2.clone(), prop1.clone()],
-            alarms: vec![],
-        };
-
-        let keys = changed_properties(&event1, &event2);
-
-        assert_eq!(keys, None);
+    fn google_times_all_day() {
+        let event = timed_event(
+            "DTSTART;VALUE=DATE:20250303",
+            Some("DTEND;VALUE=DATE:20250305"),
+        );
+        let (start, end) = google_times(&event, &Timezones::default()).unwrap();
+
+        assert_eq!(start.date, chrono::NaiveDate::from_ymd_opt(2025, 3, 3));
+        assert_eq!(end.date, chrono::NaiveDate::from_ymd_opt(2025, 3, 5));
+        assert_eq!(start.date_time, None);
+
+        // Without DTEND an all-day event lasts one day
+        let (_, end) = google_times(
+            &timed_event("DTSTART;VALUE=DATE:20250303", None),
+            &Timezones::default(),
+        )
+        .unwrap();
+        assert_eq!(end.date, chrono::NaiveDate::from_ymd_opt(2025, 3, 4));
     }
 
     #[test]
-    fn cmp_different_properties() {
-        let prop1 = Property {
-            name: String::from("prop1"),
-            value: Some(String::from("prop1 value")),
-            params: None,
-        };
-
-        let prop2 = Property {
-            name: String::from("prop2"),
-            value: Some(String::from("prop2 value")),
-            params: None,
-        };
-
-        let event1 = IcalEvent {
-            properties: vec![prop1],
-            alarms: vec![],
-        };
-
-        let event2 = IcalEvent {
-            properties: vec![prop2],
-            alarms: vec![],
-        };
+    fn google_times_timed() {
+        let event = timed_event(
+            "DTSTART;TZID=Europe/Berlin:20250303T101500",
+            Some("DTEND;TZID=Europe/Berlin:20250303T114500"),
+        );
+        let (start, end) = google_times(&event, &Timezones::default()).unwrap();
 
-        let keys = changed_properties(&event1, &event2).expect("Keys should be Some");
+        assert_eq!(start.date, None);
+        assert_eq!(
+            start.date_time.unwrap().to_rfc3339(),
+            "2025-03-03T09:15:00+00:00"
+        );
+        assert_eq!(
+            end.date_time.unwrap().to_rfc3339(),
+            "2025-03-03T10:45:00+00:00"
+        );
 
-        assert_eq!(keys.len(), 2);
-        assert!(keys.contains(&String::from("prop1")) && keys.contains(&String::from("prop2")));
+        let mixed = timed_event(
+            "DTSTART;VALUE=DATE:20250303",
+            Some("DTEND:20250303T114500Z"),
+        );
+        assert!(google_times(&mixed, &Timezones::default()).is_err());
     }
 
     #[test]
-    fn cmp_added_property() {
-        let prop1 = Property {
-            name: String::from("prop1"),
-            value: Some(String::from("prop1 value")),
-            params: None,
+    fn dtend_duration_swap_is_no_change() {
+        let calendar = |end: &str| {
+            calendar(&format!(
+                "BEGIN:VEVENT\nUID:1\nDTSTART:20250303T100000Z\n{end}\nEND:VEVENT\n"
+            ))
         };
 
-        let event1 = IcalEvent {
-            properties: vec![],
-            alarms: vec![],
-        };
-
-        let event2 = IcalEvent {
-            properties: vec![prop1],
-            alarms: vec![],
-        };
-
-        let keys = changed_properties(&event1, &event2).expect("Keys should be Some");
+        let mut detector = CalendarChangeDetector::new();
+        detector.compare(calendar("DTEND:20250303T120000Z"));
 
-        assert_eq!(keys.len(), 1);
-        assert!(keys.contains(&String::from("prop1")));
+        assert!(detector.compare(calendar("DURATION:PT2H")).is_empty());
+        assert!(matches!(
+            &detector.compare(calendar("DURATION:PT3H"))[..],
+            [CalendarEvent::Updated { .. }]
+        ));
     }
 
     #[test]
-    fn cmp_removed_property() {
-        let prop1 = Property {
-            name: String::from("prop1"),
-            value: Some(String::from("prop1 value")),
-            params: None,
-        };
-
-        let event1 = IcalEvent {
-            properties: vec![prop1],
-            alarms: vec![],
-        };
-
-        let event2 = IcalEvent {
-            properties: vec![],
-            alarms: vec![],
-        };
-
-        let keys = changed_properties(&event1, &event2).expect("Keys should be Some");
+    fn overflowing_durations_are_rejected() {
+        let calendar = calendar(
+            "X-PUBLISHED-TTL:P200000000000000D\nBEGIN:VEVENT\nUID:1\nDTSTART:20250303T100000Z\n\
+             DURATION:PT9999999999999999S\nEND:VEVENT\n",
+        );
 
-        assert_eq!(keys.len(), 1);
-        assert!(keys.contains(&String::from("prop1")));
+        let mut detector = CalendarChangeDetector::new();
+        let events = detector.compare(calendar);
+        assert_eq!(detector.ttl, DEFAULT_TTL);
+        assert!(matches!(
+            events[0].data().unwrap().event(),
+            Err(Error::InvalidDuration(_))
+        ));
+        assert!(google_times(&events[0].data().unwrap().ical_data, &Timezones::default()).is_err());
     }
 
     #[test]
-    fn cmp_different_properties_no_value() {
-        let prop1 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: None,
-        };
-
-        let prop2 = Property {
-            name: String::from("prop2"),
-            value: None,
-            params: None,
+    fn todos_are_tracked_when_configured() {
+        let calendar = |due: &str| {
+            calendar(&format!(
+                "BEGIN:VEVENT\nUID:1\nSUMMARY:Lecture\nEND:VEVENT\n\
+                 BEGIN:VTODO\nUID:1\nSUMMARY:Assignment\nDUE:{due}\nEND:VTODO\n"
+            ))
         };
 
-        let event1 = IcalEvent {
-            properties: vec![prop1],
-            alarms: vec![],
-        };
+        // Only events by default
+        let mut detector = CalendarChangeDetector::new();
+        assert_eq!(detector.compare(calendar("20250303T120000Z")).len(), 1);
 
-        let event2 = IcalEvent {
-            properties: vec![prop2],
-            alarms: vec![],
-        };
+        let mut detector = CalendarChangeDetector::with_config(DetectorConfig {
+            components: vec![ComponentKind::Event, ComponentKind::Todo],
+            ..Default::default()
+        });
+        let setup = detector.compare(calendar("20250303T120000Z"));
+        let mut kinds: Vec<(&str, ComponentKind)> = setup
+            .iter()
+            .filter_map(|change| change.data())
+            .map(|data| (data.uid.as_str(), data.kind))
+            .collect();
+        kinds.sort_by_key(|(uid, _)| *uid);
+        assert_eq!(
+            kinds,
+            vec![
+                ("1", ComponentKind::Event),
+                ("VTODO:1", ComponentKind::Todo)
+            ]
+        );
 
-        let keys = changed_properties(&event1, &event2).expect("Keys should be Some");
+        match &detector.compare(calendar("20250304T120000Z"))[..] {
+            [CalendarEvent::Updated { event, .. }] => assert_eq!(event.kind, ComponentKind::Todo),
+            changes => panic!("Unexpected changes {changes:?}"),
+        }
 
-        assert_eq!(keys.len(), 2);
-        assert!(keys.contains(&String::from("prop1")) && keys.contains(&String::from("prop2")));
+        let deleted = detector.clear();
+        assert!(deleted
+            .iter()
+            .any(|change| change.data().unwrap().kind == ComponentKind::Todo));
     }
 
     #[test]
-    fn cmp_different_params() {
-        let prop1 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
-        };
-
-        let prop2 = Property {
-            name: String::from("prop2"),
-            value: None,
-            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
-        };
+    fn deleted_components_keep_their_kind() {
+        // An event whose UID looks like the key of a task
+        let event = "BEGIN:VEVENT\nUID:VTODO:1\nSUMMARY:Lecture\nEND:VEVENT\n";
+        let other = "BEGIN:VEVENT\nUID:2\nSUMMARY:Exercise\nEND:VEVENT\n";
+
+        let mut detector = CalendarChangeDetector::with_config(DetectorConfig {
+            components: vec![ComponentKind::Event, ComponentKind::Todo],
+            ..Default::default()
+        });
+        detector.compare(calendar(&format!("{event}{other}")));
+        match &detector.compare(calendar(other))[..] {
+            [CalendarEvent::Deleted(data)] => {
+                assert_eq!(data.uid, "VTODO:1");
+                assert_eq!(data.kind, ComponentKind::Event);
+            }
+            changes => panic!("Unexpected changes {changes:?}"),
+        }
 
-        let event1 = IcalEvent {
-            properties: vec![prop1],
-            alarms: vec![],
-        };
+        detector.compare(calendar(&format!("{event}{other}")));
+        let deleted = detector.clear();
+        assert_eq!(deleted.len(), 2);
+        assert!(deleted
+            .iter()
+            .all(|change| change.data().unwrap().kind == ComponentKind::Event));
+    }
 
-        let event2 = IcalEvent {
-            properties: vec![prop2],
-            alarms: vec![],
+    #[test]
+    fn events_without_uid_are_tracked_when_configured() {
+        let calendar = |description: &str| {
+            calendar(&format!(
+                "BEGIN:VEVENT\nDTSTART:20250303T100000Z\nSUMMARY:Analysis\n\
+                 DESCRIPTION:{description}\nEND:VEVENT\n"
+            ))
         };
 
-        let keys = changed_properties(&event1, &event2).expect("Keys should be Some");
+        let mut detector = CalendarChangeDetector::new();
+        assert!(detector.compare(calendar("Room 1")).is_empty());
 
-        assert_eq!(keys.len(), 2);
-        assert!(keys.contains(&String::from("prop1")) && keys.contains(&String::from("prop2")));
+        let mut detector = CalendarChangeDetector::with_config(DetectorConfig {
+            missing_uid: IdentityStrategy::ContentHash,
+            ..Default::default()
+        });
+        assert_eq!(detector.compare(calendar("Room 1")).len(), 1);
+        assert!(matches!(
+            &detector.compare(calendar("Room 2"))[..],
+            [CalendarEvent::Updated { .. }]
+        ));
     }
 
-    #[test]
-    fn cmp_same_params() {
-        let prop1 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
+    #[tokio::test]
+    async fn backups_keep_identity_strategy_only_if_it_was_used() {
+        let name = "calendar";
+        let directory =
+            std::env::temp_dir().join(format!("ics-watcher-identity-{}", std::process::id()));
+        let config = |missing_uid| DetectorConfig {
+            missing_uid,
+            ..Default::default()
         };
-
-        let prop2 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
+        let backup = |ics: &'static str, missing_uid| {
+            let directory = directory.clone();
+            async move {
+                let mut watcher = ICSWatcher::from_source(source::StaticSource::new(ics), vec![]);
+                watcher.set_backup_dir(directory);
+                watcher.set_detector_config(config(missing_uid));
+                watcher.update().await.unwrap();
+                watcher.create_backup(name).unwrap();
+            }
         };
-
-        let event1 = IcalEvent {
-            properties: vec![prop1],
-            alarms: vec![],
+        let restore = |missing_uid| {
+            let mut watcher =
+                ICSWatcher::from_source(source::StaticSource::new(String::new()), vec![]);
+            watcher.set_backup_dir(&directory);
+            watcher.set_detector_config(config(missing_uid));
+            watcher.load_backup(name).unwrap();
+            watcher
         };
+        let strategy =
+            |watcher: &ICSWatcher| watcher.detectors().next().unwrap().1.config.missing_uid;
+
+        // Components without a UID were tracked, so their identities have to stay the same
+        backup(
+            "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:Analysis\nEND:VEVENT\nEND:VCALENDAR\n",
+            IdentityStrategy::ContentHash,
+        )
+        .await;
+        let mut watcher = restore(IdentityStrategy::Positional);
+        assert_eq!(strategy(&watcher), IdentityStrategy::ContentHash);
+        watcher.set_detector_config(config(IdentityStrategy::Positional));
+        assert_eq!(strategy(&watcher), IdentityStrategy::ContentHash);
+
+        // Nothing was tracked without a UID, the configured strategy applies
+        backup(
+            "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:1\nSUMMARY:Analysis\nEND:VEVENT\nEND:VCALENDAR\n",
+            IdentityStrategy::Skip,
+        )
+        .await;
+        let mut watcher = restore(IdentityStrategy::Positional);
+        assert_eq!(strategy(&watcher), IdentityStrategy::Positional);
+        watcher.set_detector_config(config(IdentityStrategy::ContentHash));
+        assert_eq!(strategy(&watcher), IdentityStrategy::ContentHash);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
 
-        let event2 = IcalEvent {
-            properties: vec![prop2],
-            alarms: vec![],
+    #[test]
+    fn regenerated_uids_are_reported_as_moves() {
+        let calendar = |uid: &str, description: &str| {
+            calendar(&format!(
+                "BEGIN:VEVENT\nUID:{uid}\nDTSTART:20250303T100000Z\nSUMMARY:Analysis\n\
+                 DESCRIPTION:{description}\nEND:VEVENT\n"
+            ))
         };
 
-        let keys = changed_properties(&event1, &event2);
+        let mut detector = CalendarChangeDetector::new();
+        detector.compare(calendar("ws24", "Lecture"));
+        assert_eq!(detector.compare(calendar("ss25", "Lecture")).len(), 2);
 
-        assert_eq!(keys, None);
+        let mut detector = CalendarChangeDetector::with_config(DetectorConfig {
+            match_moved: true,
+            ..Default::default()
+        });
+        detector.compare(calendar("ws24", "Lecture"));
+        match &detector.compare(calendar("ss25", "Exercise"))[..] {
+            [CalendarEvent::Moved {
+                from,
+                to,
+                changed_properties,
+            }] => {
+                assert_eq!((from.uid.as_str(), to.uid.as_str()), ("ws24", "ss25"));
+                let keys: Vec<&str> = changed_properties
+                    .iter()
+                    .map(|change| change.key.as_str())
+                    .collect();
+                assert_eq!(keys, vec!["DESCRIPTION"]);
+            }
+            changes => panic!("Unexpected changes {changes:?}"),
+        }
     }
 
     #[test]
-    fn cmp_different_param_keys() {
-        let prop1 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
-        };
-
-        let prop2 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: Some(vec![(String::from("key2"), vec![String::from("value")])]),
+    fn stale_versions_follow_policy() {
+        let calendar = |sequence: u32| {
+            calendar(&format!(
+                "BEGIN:VEVENT\nUID:1\nSEQUENCE:{sequence}\nSUMMARY:Version {sequence}\nEND:VEVENT\n"
+            ))
         };
-
-        let event1 = IcalEvent {
-            properties: vec![prop1],
-            alarms: vec![],
+        let detector = |stale_versions| {
+            let mut detector = CalendarChangeDetector::with_config(DetectorConfig {
+                stale_versions,
+                ..Default::default()
+            });
+            detector.compare(calendar(2));
+            detector
         };
 
-        let event2 = IcalEvent {
-            properties: vec![prop2],
-            alarms: vec![],
-        };
+        let mut reporting = detector(StalenessPolicy::Report);
+        assert!(matches!(
+            &reporting.compare(calendar(1))[..],
+            [CalendarEvent::Updated { .. }]
+        ));
 
-        let keys = changed_properties(&event1, &event2).expect("Keys should be Some");
+        let mut suppressing = detector(StalenessPolicy::Suppress);
+        assert!(suppressing.compare(calendar(1)).is_empty());
+        // The feed catching up again isn't a change
+        assert!(suppressing.compare(calendar(2)).is_empty());
 
-        assert_eq!(keys.len(), 1);
-        assert!(keys.contains(&String::from("prop1")));
+        let mut flagging = detector(StalenessPolicy::Flag);
+        assert!(matches!(
+            &flagging.compare(calendar(1))[..],
+            [CalendarEvent::Regressed { .. }]
+        ));
+        assert!(matches!(
+            &flagging.compare(calendar(3))[..],
+            [CalendarEvent::Updated { .. }]
+        ));
     }
 
     #[test]
-    fn cmp_different_param_values() {
-        let prop1 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: Some(vec![(String::from("key"), vec![String::from("value")])]),
-        };
-
-        let prop2 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: Some(vec![(String::from("key"), vec![String::from("value2")])]),
+    fn mass_deletions_are_held_back() {
+        let calendar = |events: usize| {
+            calendar(
+                &(0..events)
+                    .map(|uid| format!("BEGIN:VEVENT\nUID:{uid}\nSUMMARY:Lecture\nEND:VEVENT\n"))
+                    .collect::<String>(),
+            )
         };
 
-        let event1 = IcalEvent {
-            properties: vec![prop1],
-            alarms: vec![],
-        };
-
-        let event2 = IcalEvent {
-            properties: vec![prop2],
-            alarms: vec![],
-        };
+        let mut detector = CalendarChangeDetector::with_config(DetectorConfig {
+            deletion_guard: Some(DeletionGuard {
+                max_share: Some(0.5),
+                max_count: None,
+                confirmations: 2,
+            }),
+            ..Default::default()
+        });
+        detector.compare(calendar(4));
 
-        let keys = changed_properties(&event1, &event2).expect("Keys should be Some");
+        assert_eq!(detector.compare(calendar(3)).len(), 1);
+        assert_eq!(detector.held_deletion(), None);
 
-        assert_eq!(keys.len(), 1);
-        assert!(keys.contains(&String::from("prop1")));
+        // A truncated feed is held back and the previous state kept
+        let truncated = testing::calendar(
+            "X-WR-CALNAME:Empty\nX-WR-CALDESC:Nothing\nX-PUBLISHED-TTL:PT5M\n\
+             BEGIN:VTIMEZONE\nTZID:Custom\nBEGIN:STANDARD\nDTSTART:19700101T000000\n\
+             TZOFFSETFROM:+0100\nTZOFFSETTO:+0100\nEND:STANDARD\nEND:VTIMEZONE\n",
+        );
+        assert!(detector.compare(truncated).is_empty());
+        assert!(detector.held_deletion().is_some_and(|held| !held.applied));
+        assert_eq!(detector.get_state().len(), 3);
+        assert_eq!(detector.name, None);
+        assert_eq!(detector.description, None);
+        assert_eq!(detector.ttl, DEFAULT_TTL);
+        assert!(detector.timezones.get("Custom").is_none());
+
+        // Until it persists
+        assert_eq!(detector.compare(calendar(0)).len(), 3);
+        assert_eq!(
+            detector.held_deletion(),
+            Some(&HeldDeletion {
+                deleted: 3,
+                tracked: 3,
+                polls: 2,
+                applied: true
+            })
+        );
+        assert!(detector.get_state().is_empty());
     }
 
-    #[test]
-    fn cmp_added_param() {
-        let prop1 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: None,
-        };
+    #[tokio::test]
+    async fn held_deletions_are_fetched_again() {
+        let (url, server) = testing::serve(3, |index, request| match request.contains("\"v2\"") {
+            true => String::from(testing::NOT_MODIFIED),
+            false if index == 0 => testing::ok(
+                "v1",
+                "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:1\nEND:VEVENT\n\
+                 BEGIN:VEVENT\nUID:2\nEND:VEVENT\nEND:VCALENDAR\n",
+            ),
+            false => testing::ok("v2", "BEGIN:VCALENDAR\nEND:VCALENDAR\n"),
+        })
+        .await;
+        let (received, callback) = testing::recorder();
+
+        let mut watcher = ICSWatcher::from_source(HttpSource::new(url), vec![callback]);
+        watcher.set_detector_config(DetectorConfig {
+            deletion_guard: Some(DeletionGuard {
+                max_share: Some(0.5),
+                max_count: None,
+                confirmations: 2,
+            }),
+            ..Default::default()
+        });
+        watcher.update().await.unwrap();
+        received.lock().unwrap().clear();
+
+        // The truncated version is held back and fetched again, instead of being reported as unchanged
+        watcher.update().await.unwrap();
+        assert!(received.lock().unwrap().is_empty());
+        assert_eq!(watcher.source.validators().etag.as_deref(), Some("\"v1\""));
+
+        watcher.update().await.unwrap();
+        let deleted = received
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|(_, events)| events)
+            .filter(|event| matches!(event, CalendarEvent::Deleted(_)))
+            .count();
+        assert_eq!(deleted, 2);
+        assert_eq!(watcher.source.validators().etag.as_deref(), Some("\"v2\""));
+        server.await.unwrap();
+    }
 
-        let prop2 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: Some(vec![(String::from("key"), vec![String::from("value2")])]),
+    #[tokio::test]
+    async fn calendar_renames_are_reported() {
+        let calendar = |name: &str, ttl: &str| {
+            format!(
+                "BEGIN:VCALENDAR\nX-WR-CALNAME:{name}\nX-PUBLISHED-TTL:{ttl}\n\
+                 BEGIN:VEVENT\nUID:1\nSUMMARY:Analysis\nEND:VEVENT\nEND:VCALENDAR\n"
+            )
         };
+        let source = source::StaticSource::new(calendar("Lectures", "PT1H"));
+        let contents = source.0.clone();
+        let (received, callback) = testing::recorder();
+
+        let mut watcher = ICSWatcher::from_source(source, vec![callback]);
+        watcher.update().await.unwrap();
+        received.lock().unwrap().clear();
+
+        *contents.lock().unwrap() = calendar("Lectures WS25", "PT15M");
+        watcher.update().await.unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0.as_deref(), Some("Lectures WS25"));
+        match &received[0].1[..] {
+            [CalendarEvent::MetadataChanged(changes)] => {
+                let keys: Vec<&str> = changes.iter().map(|change| change.key.as_str()).collect();
+                assert_eq!(keys, vec!["X-WR-CALNAME", "X-PUBLISHED-TTL"]);
+                assert_eq!(
+                    changes[0]
+                        .to
+                        .as_ref()
+                        .and_then(|prop| prop.value.as_deref()),
+                    Some("Lectures WS25")
+                );
+            }
+            changes => panic!("Unexpected changes {changes:?}"),
+        }
+        let keys: Vec<&str> = watcher.detectors().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec!["Lectures WS25"]);
+    }
 
-        let event1 = IcalEvent {
-            properties: vec![prop1],
-            alarms: vec![],
-        };
+    #[tokio::test]
+    async fn failed_versions_are_fetched_again() {
+        let (url, server) = testing::serve(2, |_, request| {
+            // The broken version must not be sent back as unchanged
+            match request.contains("if-none-match") {
+                true => testing::ok("v1", ""),
+                false => testing::ok(
+                    "v1",
+                    "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:1\nEND:VEVENT\nEND:VCALENDAR\n",
+                ),
+            }
+        })
+        .await;
+
+        let received = testing::Received::default();
+        let callback: CalendarCallback = Box::new({
+            let received = received.clone();
+            move |name, _, events| {
+                let mut received = received.lock().unwrap();
+                let failed = received.is_empty();
+                received.push((name, events));
+                Box::pin(async move {
+                    match failed {
+                        true => Err(Error::callback("Unavailable")),
+                        false => Ok(()),
+                    }
+                })
+            }
+        });
 
-        let event2 = IcalEvent {
-            properties: vec![prop2],
-            alarms: vec![],
-        };
+        let mut watcher = ICSWatcher::from_source(HttpSource::new(url), vec![callback]);
+        watcher.update().await.unwrap();
+        assert_eq!(watcher.source.validators(), CacheValidators::default());
 
-        let keys = changed_properties(&event1, &event2).expect("Keys should be Some");
+        watcher.update().await.unwrap();
+        assert_eq!(watcher.source.validators().etag.as_deref(), Some("\"v1\""));
+        server.await.unwrap();
 
-        assert_eq!(keys.len(), 1);
-        assert!(keys.contains(&String::from("prop1")));
+        // The failed changes are delivered again
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        for (_, events) in received.iter() {
+            assert!(matches!(&events[..], [CalendarEvent::Setup(data)] if data.uid == "1"));
+        }
     }
 
-    #[test]
-    fn cmp_removed_param() {
-        let prop1 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: Some(vec![(String::from("key"), vec![String::from("value2")])]),
-        };
+    #[tokio::test]
+    async fn calendars_are_tracked_separately() {
+        let source = source::StaticSource::new(
+            "BEGIN:VCALENDAR\nX-WR-CALNAME:Lectures\nBEGIN:VEVENT\nUID:1\nSUMMARY:Analysis\nEND:VEVENT\nEND:VCALENDAR\n\
+             BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:1\nSUMMARY:Exam\nEND:VEVENT\nEND:VCALENDAR\n",
+        );
+        let contents = source.0.clone();
+        let (received, callback) = testing::recorder();
 
-        let prop2 = Property {
-            name: String::from("prop1"),
-            value: None,
-            params: None,
-        };
+        let mut watcher = ICSWatcher::from_source(source, vec![callback]);
+        watcher.update().await.unwrap();
 
-        let event1 = IcalEvent {
-            properties: vec![prop1],
-            alarms: vec![],
-        };
+        let keys: Vec<&str> = watcher.detectors().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec!["Lectures", "#1"]);
+        assert_eq!(received.lock().unwrap().len(), 2);
 
-        let event2 = IcalEvent {
-            properties: vec![prop2],
-            alarms: vec![],
-        };
+        // Drop the second calendar and change the first one
+        *contents.lock().unwrap() = String::from(
+            "BEGIN:VCALENDAR\nX-WR-CALNAME:Lectures\nBEGIN:VEVENT\nUID:1\nSUMMARY:Algebra\nEND:VEVENT\nEND:VCALENDAR\n",
+        );
+        received.lock().unwrap().clear();
+        watcher.update().await.unwrap();
 
-        let keys = changed_properties(&event1, &event2).expect("Keys should be Some");
+        {
+            let received = received.lock().unwrap();
+            assert_eq!(received.len(), 2);
+            assert_eq!(received[0].0.as_deref(), Some("Lectures"));
+            assert!(matches!(received[0].1[..], [CalendarEvent::Updated { .. }]));
+            assert_eq!(received[1].0, None);
+            assert!(matches!(received[1].1[..], [CalendarEvent::Deleted(_)]));
+        }
+        assert_eq!(watcher.detectors().count(), 1);
+
+        // A calendar added later on is no setup
+        *contents.lock().unwrap() = String::from(
+            "BEGIN:VCALENDAR\nX-WR-CALNAME:Lectures\nBEGIN:VEVENT\nUID:1\nSUMMARY:Algebra\nEND:VEVENT\nEND:VCALENDAR\n\
+             BEGIN:VCALENDAR\nX-WR-CALNAME:Exams\nBEGIN:VEVENT\nUID:1\nSUMMARY:Exam\nEND:VEVENT\nEND:VCALENDAR\n",
+        );
+        received.lock().unwrap().clear();
+        watcher.update().await.unwrap();
 
-        assert_eq!(keys.len(), 1);
-        assert!(keys.contains(&String::from("prop1")));
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0.as_deref(), Some("Exams"));
+        assert!(matches!(received[0].1[..], [CalendarEvent::Created(_)]));
     }
 }
@@ -1,10 +1,28 @@
 use dotenv::dotenv;
 use ics_watcher::{tum_google_sync, ICSWatcher};
 use std::env;
+use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+
+    #[cfg(feature = "metrics")]
+    if let Ok(addr) = env::var("METRICS_ADDR") {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .expect("METRICS_ADDR can't be bound");
+        tokio::spawn(async move {
+            if let Err(error) = ics_watcher::metrics::serve_listener(listener).await {
+                tracing::error!(%error, "Serving metrics failed");
+            }
+        });
+    }
 
     let tum_url = env::var("TUM_URL").expect("TUM_URL not found in environment");
     let google_calendar_id =
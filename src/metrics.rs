@@ -0,0 +1,234 @@
+//! Prometheus metrics of all watchers in this process (requires the `metrics` feature).
+//!
+//! The metrics are collected in a [Registry] (see [registry]), which can be exposed
+//! on a local `/metrics` endpoint using [serve].
+//!
+//! Every metric of a watcher is labelled with its `feed`, the link or path of its [CalendarSource](crate::CalendarSource)
+//! without credentials or query parameters (which often contain access tokens).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[tokio::main]
+//! # async fn main() {
+//! tokio::spawn(async {
+//!     if let Err(error) = ics_watcher::metrics::serve("127.0.0.1:9898").await {
+//!         eprintln!("Serving metrics failed: {error}");
+//!     }
+//! });
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    exponential_buckets, Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry,
+    TextEncoder,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, ToSocketAddrs},
+};
+use tracing::{debug, warn};
+
+use crate::{CalendarEvent, Error};
+
+struct Metrics {
+    registry: Registry,
+    polls: IntCounterVec,
+    fetch_duration: HistogramVec,
+    fetched_bytes: IntCounterVec,
+    changes: IntCounterVec,
+    callback_failures: IntCounterVec,
+    google_api_errors: IntCounterVec,
+}
+
+static METRICS: Lazy<Metrics> = Lazy::new(|| {
+    let registry = Registry::new_custom(Some(String::from("ics_watcher")), None)
+        .expect("Metric prefix is valid");
+
+    let polls = IntCounterVec::new(
+        Opts::new("polls_total", "Updates of a feed, by outcome"),
+        &["feed", "outcome"],
+    )
+    .expect("Metric is valid");
+    let fetch_duration = HistogramVec::new(
+        HistogramOpts::new(
+            "fetch_duration_seconds",
+            "Time it took to fetch a feed, by outcome",
+        )
+        .buckets(exponential_buckets(0.01, 2.0, 12).expect("Buckets are valid")),
+        &["feed", "outcome"],
+    )
+    .expect("Metric is valid");
+    let fetched_bytes = IntCounterVec::new(
+        Opts::new("fetched_bytes_total", "Size of the fetched calendars"),
+        &["feed"],
+    )
+    .expect("Metric is valid");
+    let changes = IntCounterVec::new(
+        Opts::new("changes_total", "Detected changes, by kind"),
+        &["feed", "kind"],
+    )
+    .expect("Metric is valid");
+    let callback_failures = IntCounterVec::new(
+        Opts::new(
+            "callback_failures_total",
+            "Callbacks which returned an error",
+        ),
+        &["feed"],
+    )
+    .expect("Metric is valid");
+    let google_api_errors = IntCounterVec::new(
+        Opts::new(
+            "google_api_errors_total",
+            "Failed Google Calendar API calls of the TUM Google sync, by operation",
+        ),
+        &["operation"],
+    )
+    .expect("Metric is valid");
+
+    for collector in [
+        Box::new(polls.clone()) as Box<dyn prometheus::core::Collector>,
+        Box::new(fetch_duration.clone()),
+        Box::new(fetched_bytes.clone()),
+        Box::new(changes.clone()),
+        Box::new(callback_failures.clone()),
+        Box::new(google_api_errors.clone()),
+    ] {
+        registry
+            .register(collector)
+            .expect("Metrics are only registered once");
+    }
+
+    Metrics {
+        registry,
+        polls,
+        fetch_duration,
+        fetched_bytes,
+        changes,
+        callback_failures,
+        google_api_errors,
+    }
+});
+
+/// The registry containing all metrics, e.g. to expose them using your own HTTP server
+pub fn registry() -> &'static Registry {
+    &METRICS.registry
+}
+
+/// Renders all metrics in the Prometheus text format
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&registry().gather(), &mut buffer)
+        .expect("Encoding metrics into memory can't fail");
+    String::from_utf8(buffer).expect("Metrics are valid UTF-8")
+}
+
+/// Serves the metrics on `http://<addr>/metrics` until the listener fails
+pub async fn serve(addr: impl ToSocketAddrs) -> Result<(), Error> {
+    serve_listener(TcpListener::bind(addr).await.map_err(Error::Io)?).await
+}
+
+/// Serves the metrics on `/metrics` of an already bound `listener` until it fails
+pub async fn serve_listener(listener: TcpListener) -> Result<(), Error> {
+    debug!(addr = ?listener.local_addr().ok(), "Serving metrics");
+
+    loop {
+        let (mut stream, _) = listener.accept().await.map_err(Error::Io)?;
+        tokio::spawn(async move {
+            let mut request = vec![0; 1024];
+            let read = match stream.read(&mut request).await {
+                Ok(read) => read,
+                Err(error) => return warn!(%error, "Reading metrics request failed"),
+            };
+
+            let request = String::from_utf8_lossy(&request[..read]);
+            let response = match request.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+                ["GET", "/metrics"] => {
+                    let body = render();
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        prometheus::TEXT_FORMAT,
+                        body.len()
+                    )
+                }
+                _ => String::from(
+                    "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                ),
+            };
+            if let Err(error) = stream.write_all(response.as_bytes()).await {
+                warn!(%error, "Answering metrics request failed");
+            }
+        });
+    }
+}
+
+pub(crate) fn record_poll(feed: &str, outcome: &str) {
+    METRICS.polls.with_label_values(&[feed, outcome]).inc();
+}
+
+/// Records a fetch attempt, `outcome` is `fetched`, `not_modified` or `error`
+pub(crate) fn record_fetch(feed: &str, outcome: &str, duration: Duration) {
+    METRICS
+        .fetch_duration
+        .with_label_values(&[feed, outcome])
+        .observe(duration.as_secs_f64());
+}
+
+pub(crate) fn record_fetched_bytes(feed: &str, bytes: usize) {
+    METRICS
+        .fetched_bytes
+        .with_label_values(&[feed])
+        .inc_by(bytes as u64);
+}
+
+pub(crate) fn record_changes(feed: &str, events: &[CalendarEvent]) {
+    for event in events {
+        let kind = match event {
+            CalendarEvent::Setup(_) => "setup",
+            CalendarEvent::Created(_) => "created",
+            CalendarEvent::Updated { .. } => "updated",
+            CalendarEvent::Deleted(_) => "deleted",
+            CalendarEvent::Regressed { .. } => "regressed",
+            CalendarEvent::Moved { .. } => "moved",
+            CalendarEvent::MetadataChanged(_) => "metadata",
+        };
+        METRICS.changes.with_label_values(&[feed, kind]).inc();
+    }
+}
+
+pub(crate) fn record_callback_failure(feed: &str) {
+    METRICS.callback_failures.with_label_values(&[feed]).inc();
+}
+
+pub(crate) fn record_google_api_error(operation: &str) {
+    METRICS
+        .google_api_errors
+        .with_label_values(&[operation])
+        .inc();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn serves_metrics() {
+        record_poll("test-feed", "fetched");
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_listener(listener));
+
+        let body = reqwest::get(format!("http://{addr}/metrics"))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(body.contains("ics_watcher_polls_total{feed=\"test-feed\",outcome=\"fetched\"} 1"));
+    }
+}
@@ -0,0 +1,776 @@
+//! Expanding recurring events (RRULE, RDATE, EXDATE) into their single occurrences.
+//!
+//! See [RecurrenceExpansion] and [DetectorConfig::expand_recurrences](crate::DetectorConfig::expand_recurrences).
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::RangeInclusive,
+};
+
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, NaiveDateTime, TimeDelta, Utc, Weekday};
+use chrono_tz::Tz;
+use ical::{
+    parser::{ical::component::IcalEvent, Component},
+    property::Property,
+};
+use tracing::debug;
+
+use crate::datetime::{
+    format_value, local_time, param, parse_value, CalendarTime, Timezones, ValueFormat,
+};
+
+/// Upper bound of periods (days, weeks, ...) a single RRULE is expanded over
+const MAX_PERIODS: u32 = 100_000;
+
+/// Expands recurring events into one event per occurrence, so changes of single dates can be detected.
+///
+/// Only occurrences between `past` before and `future` after the current time are tracked.
+/// Every occurrence is a copy of the recurring event without RRULE, RDATE and EXDATE,
+/// with its own DTSTART/DTEND and a RECURRENCE-ID identifying it.
+/// Occurrences which are overridden by an event with the same UID and RECURRENCE-ID are replaced by it.
+///
+/// Occurrences are computed in the local time of DTSTART, the window is compared against UTC.
+/// UNTIL, RDATE, EXDATE and RECURRENCE-ID match occurrences by their point in time, whichever
+/// time zone they are written in.
+/// As the window moves with the current time, occurrences (and overrides) entering or leaving it
+/// aren't reported as created or deleted, only changes within both the previous and the current window are.
+/// RRULEs using BYHOUR, BYMINUTE, BYSECOND, BYYEARDAY, BYWEEKNO or BYSETPOS aren't supported,
+/// such events are tracked as a whole.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceExpansion {
+    pub past: TimeDelta,
+    pub future: TimeDelta,
+}
+
+impl Default for RecurrenceExpansion {
+    fn default() -> Self {
+        RecurrenceExpansion {
+            past: TimeDelta::days(30),
+            future: TimeDelta::days(365),
+        }
+    }
+}
+
+impl RecurrenceExpansion {
+    pub fn new(past: TimeDelta, future: TimeDelta) -> Self {
+        RecurrenceExpansion { past, future }
+    }
+
+    /// The occurrences which are tracked at `now`
+    pub(crate) fn window(&self, now: NaiveDateTime) -> RangeInclusive<NaiveDateTime> {
+        now - self.past..=now + self.future
+    }
+}
+
+/// The point in time of a DATE or DATE-TIME, where dates and floating times are taken as UTC
+fn instant(time: CalendarTime) -> DateTime<Utc> {
+    time.to_utc(Tz::UTC)
+}
+
+/// The occurrence an expanded (or overriding) event stands for, according to its RECURRENCE-ID
+pub(crate) fn recurrence_id(event: &IcalEvent, timezones: &Timezones) -> Option<DateTime<Utc>> {
+    let prop = event.get_property("RECURRENCE-ID")?;
+    CalendarTime::from_property(prop, timezones)
+        .ok()
+        .map(instant)
+}
+
+/// All values of a list property (RDATE, EXDATE), which can occur multiple times
+fn list_values(event: &IcalEvent, name: &str, timezones: &Timezones) -> Vec<DateTime<Utc>> {
+    event
+        .properties
+        .iter()
+        .filter(|prop| prop.name == name)
+        .flat_map(|prop| {
+            let tzid = param(prop, "TZID");
+            prop.value
+                .as_deref()
+                .unwrap_or_default()
+                .split(',')
+                // Periods (VALUE=PERIOD) start at their first part
+                .filter_map(move |value| {
+                    let start = value.split('/').next().unwrap_or(value);
+                    CalendarTime::parse(start, tzid, timezones).ok()
+                })
+                .map(instant)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Rule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<(NaiveDateTime, ValueFormat)>,
+    by_day: Vec<(Option<i32>, Weekday)>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+    week_start: Weekday,
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    match value {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+impl Rule {
+    /// Parses an RRULE value, returns [None] if it is invalid or uses unsupported parts
+    pub(crate) fn parse(value: &str) -> Option<Rule> {
+        let mut rule = Rule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+            week_start: Weekday::Mon,
+        };
+        let mut frequency = None;
+
+        for part in value.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part.split_once('=')?;
+            let list = || value.split(',').map(str::trim);
+            match key.to_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return None,
+                    })
+                }
+                "INTERVAL" => rule.interval = value.parse().ok().filter(|i| *i > 0)?,
+                "COUNT" => rule.count = Some(value.parse().ok()?),
+                "UNTIL" => rule.until = Some(parse_value(value)?),
+                "WKST" => rule.week_start = parse_weekday(value)?,
+                "BYDAY" => {
+                    rule.by_day = list()
+                        .map(|day| {
+                            let (ordinal, weekday) = day.split_at(day.len().checked_sub(2)?);
+                            let ordinal = match ordinal {
+                                "" => None,
+                                ordinal => Some(ordinal.parse::<i32>().ok()?),
+                            };
+                            Some((ordinal, parse_weekday(weekday)?))
+                        })
+                        .collect::<Option<_>>()?
+                }
+                "BYMONTHDAY" => {
+                    rule.by_month_day = list().map(|day| day.parse().ok()).collect::<Option<_>>()?
+                }
+                "BYMONTH" => {
+                    rule.by_month = list()
+                        .map(|month| month.parse().ok())
+                        .collect::<Option<_>>()?
+                }
+                _ => return None,
+            }
+        }
+
+        rule.frequency = frequency?;
+        Some(rule)
+    }
+
+    /// The dates of `month` matching BYMONTHDAY and BYDAY, or the day of `start` if neither is set
+    fn month_dates(&self, year: i32, month: u32, start: NaiveDate) -> Vec<NaiveDate> {
+        let Some(first) = NaiveDate::from_ymd_opt(year, month, 1) else {
+            return Vec::new();
+        };
+        let last = first
+            .checked_add_months(Months::new(1))
+            .and_then(|next| next.pred_opt())
+            .unwrap_or(first);
+
+        if !self.by_month_day.is_empty() {
+            return self
+                .by_month_day
+                .iter()
+                .filter_map(|day| match *day {
+                    day if day > 0 => first.with_day(day as u32),
+                    day if day < 0 => last.checked_sub_days(Days::new((-day - 1) as u64)),
+                    _ => None,
+                })
+                .filter(|date| date.month() == month)
+                .filter(|date| {
+                    self.by_day.is_empty()
+                        || self
+                            .by_day
+                            .iter()
+                            .any(|(_, weekday)| date.weekday() == *weekday)
+                })
+                .collect();
+        }
+
+        if !self.by_day.is_empty() {
+            return weekday_dates(&self.by_day, first, last);
+        }
+
+        first.with_day(start.day()).into_iter().collect()
+    }
+
+    /// The candidate dates of the `index`-th period after `start`
+    fn period_dates(&self, start: NaiveDate, index: u32) -> Vec<NaiveDate> {
+        let step = index.saturating_mul(self.interval);
+        let in_months =
+            |date: &NaiveDate| self.by_month.is_empty() || self.by_month.contains(&date.month());
+
+        match self.frequency {
+            Frequency::Daily => start
+                .checked_add_days(Days::new(step as u64))
+                .into_iter()
+                .filter(in_months)
+                .filter(|date| {
+                    self.by_month_day.is_empty()
+                        || self
+                            .month_dates(date.year(), date.month(), start)
+                            .contains(date)
+                })
+                .filter(|date| {
+                    self.by_day.is_empty()
+                        || self
+                            .by_day
+                            .iter()
+                            .any(|(_, weekday)| date.weekday() == *weekday)
+                })
+                .collect(),
+            Frequency::Weekly => {
+                let offset = start.weekday().days_since(self.week_start);
+                let Some(week) = start
+                    .checked_sub_days(Days::new(offset as u64))
+                    .and_then(|week| week.checked_add_days(Days::new(7 * step as u64)))
+                else {
+                    return Vec::new();
+                };
+                let weekdays = match self.by_day.is_empty() {
+                    true => vec![start.weekday()],
+                    false => self.by_day.iter().map(|(_, weekday)| *weekday).collect(),
+                };
+                weekdays
+                    .into_iter()
+                    .filter_map(|weekday| {
+                        week.checked_add_days(Days::new(weekday.days_since(self.week_start) as u64))
+                    })
+                    .filter(in_months)
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let Some(month) = start
+                    .with_day(1)
+                    .and_then(|month| month.checked_add_months(Months::new(step)))
+                else {
+                    return Vec::new();
+                };
+                if !in_months(&month) {
+                    return Vec::new();
+                }
+                self.month_dates(month.year(), month.month(), start)
+            }
+            Frequency::Yearly => {
+                let Some(year) = start.year().checked_add(step as i32) else {
+                    return Vec::new();
+                };
+                if self.by_month.is_empty()
+                    && self.by_month_day.is_empty()
+                    && !self.by_day.is_empty()
+                {
+                    // Weekdays of the whole year (e.g. the 20th monday of the year)
+                    return match (
+                        NaiveDate::from_ymd_opt(year, 1, 1),
+                        NaiveDate::from_ymd_opt(year, 12, 31),
+                    ) {
+                        (Some(first), Some(last)) => weekday_dates(&self.by_day, first, last),
+                        _ => Vec::new(),
+                    };
+                }
+                let months = match self.by_month.is_empty() {
+                    true => vec![start.month()],
+                    false => self.by_month.clone(),
+                };
+                months
+                    .into_iter()
+                    .flat_map(|month| self.month_dates(year, month, start))
+                    .collect()
+            }
+        }
+    }
+
+    /// Whether the local time `at` is after UNTIL, which is compared in UTC (see `to_utc`) if written in UTC
+    fn is_over(&self, at: NaiveDateTime, to_utc: &impl Fn(NaiveDateTime) -> DateTime<Utc>) -> bool {
+        match self.until {
+            Some((until, ValueFormat::Utc)) => to_utc(at) > until.and_utc(),
+            Some((until, _)) => at > until,
+            None => false,
+        }
+    }
+
+    /// All occurrences from `start` until `end` (or UNTIL / COUNT), in the local time of `start`.
+    ///
+    /// `to_utc` resolves a local time of `start` to UTC.
+    pub(crate) fn occurrences(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+        to_utc: impl Fn(NaiveDateTime) -> DateTime<Utc>,
+    ) -> Vec<NaiveDateTime> {
+        // DTSTART is always the first occurrence
+        let mut occurrences = vec![start];
+
+        'periods: for index in 0..MAX_PERIODS {
+            let mut dates = self.period_dates(start.date(), index);
+            dates.sort();
+            dates.dedup();
+
+            for date in dates {
+                let at = date.and_time(start.time());
+                if at <= start {
+                    continue;
+                }
+                if at > end
+                    || self.is_over(at, &to_utc)
+                    || self
+                        .count
+                        .is_some_and(|count| occurrences.len() >= count as usize)
+                {
+                    break 'periods;
+                }
+                occurrences.push(at);
+            }
+        }
+
+        occurrences
+    }
+}
+
+/// The dates between `first` and `last` matching BYDAY, where ordinals count from `first` (or from `last` if negative)
+fn weekday_dates(
+    by_day: &[(Option<i32>, Weekday)],
+    first: NaiveDate,
+    last: NaiveDate,
+) -> Vec<NaiveDate> {
+    by_day
+        .iter()
+        .flat_map(|(ordinal, weekday)| {
+            let dates: Vec<NaiveDate> = first
+                .iter_days()
+                .take_while(|date| *date <= last)
+                .filter(|date| date.weekday() == *weekday)
+                .collect();
+            match ordinal {
+                None => dates,
+                Some(n) if *n > 0 => dates.get(*n as usize - 1).copied().into_iter().collect(),
+                Some(n) if *n < 0 => dates
+                    .len()
+                    .checked_sub(n.unsigned_abs() as usize)
+                    .and_then(|index| dates.get(index).copied())
+                    .into_iter()
+                    .collect(),
+                Some(_) => Vec::new(),
+            }
+        })
+        .collect()
+}
+
+fn is_recurring(event: &IcalEvent) -> bool {
+    event
+        .properties
+        .iter()
+        .any(|prop| prop.name == "RRULE" || prop.name == "RDATE")
+}
+
+/// The occurrences of a recurring event within the window, [None] if they can't be computed
+fn expand_event(
+    event: &IcalEvent,
+    expansion: &RecurrenceExpansion,
+    now: NaiveDateTime,
+    timezones: &Timezones,
+) -> Option<Vec<IcalEvent>> {
+    let dtstart = event.get_property("DTSTART")?;
+    let (start, start_format) = parse_value(dtstart.value.as_deref()?)?;
+    let tzid = param(dtstart, "TZID");
+    // Bails out if the time zone of DTSTART is unknown
+    CalendarTime::resolve(start, start_format, tzid, timezones).ok()?;
+    let to_utc = |at: NaiveDateTime| {
+        CalendarTime::resolve(at, start_format, tzid, timezones)
+            .map(instant)
+            .unwrap_or_else(|_| at.and_utc())
+    };
+    // The local time of an RDATE, which can be written in another time zone
+    let to_local = |at: DateTime<Utc>| match (start_format, tzid) {
+        (ValueFormat::Local, Some(tzid)) => {
+            local_time(at, tzid, timezones).unwrap_or_else(|| at.naive_utc())
+        }
+        _ => at.naive_utc(),
+    };
+    let dtend = match event.get_property("DTEND") {
+        Some(prop) => Some((prop, parse_value(prop.value.as_deref()?)?)),
+        None => None,
+    };
+
+    let window = expansion.window(now);
+    // Local times are up to a day ahead of UTC
+    let window_end = window
+        .end()
+        .checked_add_signed(TimeDelta::days(1))
+        .unwrap_or(*window.end());
+
+    let rules = event
+        .properties
+        .iter()
+        .filter(|prop| prop.name == "RRULE")
+        .map(|prop| Rule::parse(prop.value.as_deref()?))
+        .collect::<Option<Vec<_>>>()?;
+
+    // The local times of the occurrences by their point in time
+    let mut occurrences = BTreeMap::from([(to_utc(start), start)]);
+    for rule in rules {
+        occurrences.extend(
+            rule.occurrences(start, window_end, to_utc)
+                .into_iter()
+                .map(|at| (to_utc(at), at)),
+        );
+    }
+    occurrences.extend(
+        list_values(event, "RDATE", timezones)
+            .into_iter()
+            .map(|at| (at, to_local(at))),
+    );
+    for excluded in list_values(event, "EXDATE", timezones) {
+        occurrences.remove(&excluded);
+    }
+
+    let properties: Vec<&Property> = event
+        .properties
+        .iter()
+        .filter(|prop| {
+            !matches!(
+                prop.name.as_str(),
+                "RRULE" | "RDATE" | "EXDATE" | "EXRULE" | "DTSTART" | "DTEND" | "RECURRENCE-ID"
+            )
+        })
+        .collect();
+
+    Some(
+        occurrences
+            .into_iter()
+            .filter(|(instant, _)| window.contains(&instant.naive_utc()))
+            .map(|(_, at)| {
+                let value = format_value(at, start_format);
+                let mut properties: Vec<Property> =
+                    properties.iter().map(|p| (*p).clone()).collect();
+                properties.push(Property {
+                    name: String::from("DTSTART"),
+                    params: dtstart.params.clone(),
+                    value: Some(value.clone()),
+                });
+                if let Some((dtend, (end, end_format))) = &dtend {
+                    properties.push(Property {
+                        name: String::from("DTEND"),
+                        params: dtend.params.clone(),
+                        value: Some(format_value(at + (*end - start), *end_format)),
+                    });
+                }
+                properties.push(Property {
+                    name: String::from("RECURRENCE-ID"),
+                    params: dtstart.params.clone(),
+                    value: Some(value),
+                });
+
+                IcalEvent {
+                    properties,
+                    alarms: event.alarms.clone(),
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Replaces all recurring events by their occurrences within the window of `expansion`
+pub(crate) fn expand(
+    events: Vec<IcalEvent>,
+    expansion: &RecurrenceExpansion,
+    now: NaiveDateTime,
+    timezones: &Timezones,
+) -> Vec<IcalEvent> {
+    let uid = |event: &IcalEvent| {
+        event
+            .get_property("UID")
+            .and_then(|prop| prop.value.clone())
+    };
+    let overridden: HashSet<(String, DateTime<Utc>)> = events
+        .iter()
+        .filter_map(|event| Some((uid(event)?, recurrence_id(event, timezones)?)))
+        .collect();
+
+    let mut expanded = Vec::with_capacity(events.len());
+    for event in events {
+        if event.get_property("RECURRENCE-ID").is_some() || !is_recurring(&event) {
+            expanded.push(event);
+            continue;
+        }
+
+        match expand_event(&event, expansion, now, timezones) {
+            Some(occurrences) => {
+                let event_uid = uid(&event);
+                expanded.extend(occurrences.into_iter().filter(|occurrence| {
+                    match (&event_uid, recurrence_id(occurrence, timezones)) {
+                        (Some(uid), Some(at)) => !overridden.contains(&(uid.clone(), at)),
+                        _ => true,
+                    }
+                }));
+            }
+            None => {
+                debug!(uid = ?uid(&event), "Unable to expand recurring event, tracking it as a whole");
+                expanded.push(event);
+            }
+        }
+    }
+
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{datetime::ValueFormat, testing};
+
+    fn at(value: &str) -> NaiveDateTime {
+        parse_value(value).unwrap().0
+    }
+
+    fn property(name: &str, value: &str) -> Property {
+        Property {
+            name: String::from(name),
+            params: None,
+            value: Some(String::from(value)),
+        }
+    }
+
+    fn event(properties: Vec<Property>) -> IcalEvent {
+        IcalEvent {
+            properties,
+            alarms: vec![],
+        }
+    }
+
+    fn starts(events: &[IcalEvent]) -> Vec<String> {
+        events
+            .iter()
+            .filter_map(|event| event.get_property("DTSTART")?.value.clone())
+            .collect()
+    }
+
+    #[test]
+    fn weekly_with_count() {
+        let rule = Rule::parse("FREQ=WEEKLY;BYDAY=TU,TH;COUNT=4").unwrap();
+        let occurrences = rule.occurrences(at("20250107T100000"), at("20260101T000000"), |at| {
+            at.and_utc()
+        });
+
+        assert_eq!(
+            occurrences,
+            vec![
+                at("20250107T100000"),
+                at("20250109T100000"),
+                at("20250114T100000"),
+                at("20250116T100000")
+            ]
+        );
+    }
+
+    #[test]
+    fn monthly_last_friday_until() {
+        let rule = Rule::parse("FREQ=MONTHLY;BYDAY=-1FR;UNTIL=20250430T235959Z").unwrap();
+        let occurrences = rule.occurrences(at("20250131T090000"), at("20260101T000000"), |at| {
+            at.and_utc()
+        });
+
+        assert_eq!(
+            occurrences,
+            vec![
+                at("20250131T090000"),
+                at("20250228T090000"),
+                at("20250328T090000"),
+                at("20250425T090000")
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_rules_are_rejected() {
+        assert_eq!(Rule::parse("FREQ=DAILY;BYHOUR=10,12"), None);
+        assert_eq!(Rule::parse("INTERVAL=2"), None);
+    }
+
+    #[test]
+    fn expands_within_window_with_exdates_and_overrides() {
+        let lecture = event(vec![
+            property("UID", "lecture"),
+            property("SUMMARY", "Analysis"),
+            property("DTSTART", "20250106T100000"),
+            property("DTEND", "20250106T120000"),
+            property("RRULE", "FREQ=WEEKLY"),
+            property("EXDATE", "20250120T100000"),
+        ]);
+        let moved = event(vec![
+            property("UID", "lecture"),
+            property("SUMMARY", "Analysis (moved)"),
+            property("RECURRENCE-ID", "20250127T100000"),
+            property("DTSTART", "20250128T100000"),
+            property("DTEND", "20250128T120000"),
+        ]);
+
+        let expansion = RecurrenceExpansion::new(TimeDelta::days(7), TimeDelta::days(21));
+        let events = expand(
+            vec![lecture, moved],
+            &expansion,
+            at("20250114T000000"),
+            &Timezones::default(),
+        );
+
+        // The 20th is excluded and the 27th overridden
+        assert_eq!(
+            starts(&events),
+            vec!["20250113T100000", "20250203T100000", "20250128T100000"]
+        );
+        let first = &events[0];
+        assert_eq!(
+            first.get_property("DTEND").unwrap().value.as_deref(),
+            Some("20250113T120000")
+        );
+        assert_eq!(
+            first
+                .get_property("RECURRENCE-ID")
+                .unwrap()
+                .value
+                .as_deref(),
+            Some("20250113T100000")
+        );
+        assert!(first.get_property("RRULE").is_none());
+    }
+
+    #[test]
+    fn utc_values_match_occurrences_in_time_zones() {
+        let zoned = |name: &str, value: &str| Property {
+            params: Some(vec![(
+                String::from("TZID"),
+                vec![String::from("Europe/Berlin")],
+            )]),
+            ..property(name, value)
+        };
+        // 10:00 in Berlin is 09:00 in UTC
+        let lecture = event(vec![
+            property("UID", "lecture"),
+            zoned("DTSTART", "20250106T100000"),
+            property("RRULE", "FREQ=WEEKLY;UNTIL=20250203T090000Z"),
+            property("EXDATE", "20250113T090000Z"),
+            zoned("RDATE", "20250122T100000"),
+        ]);
+        let moved = event(vec![
+            property("UID", "lecture"),
+            property("RECURRENCE-ID", "20250120T090000Z"),
+            zoned("DTSTART", "20250121T100000"),
+        ]);
+
+        let expansion = RecurrenceExpansion::new(TimeDelta::days(7), TimeDelta::days(60));
+        let events = expand(
+            vec![lecture, moved],
+            &expansion,
+            at("20250107T000000"),
+            &Timezones::default(),
+        );
+
+        // The 13th is excluded, the 20th overridden and the 3rd of February is the last one
+        assert_eq!(
+            starts(&events),
+            vec![
+                "20250106T100000",
+                "20250122T100000",
+                "20250127T100000",
+                "20250203T100000",
+                "20250121T100000"
+            ]
+        );
+    }
+
+    #[test]
+    fn moving_window_is_no_change() {
+        let calendar = || {
+            testing::calendar(
+                "BEGIN:VEVENT\nUID:lecture\nDTSTART:20250106T100000\nRRULE:FREQ=WEEKLY\nEND:VEVENT\n",
+            )
+        };
+        let now = at("20250114T000000").and_utc();
+
+        let mut detector = crate::CalendarChangeDetector::with_config(crate::DetectorConfig {
+            expand_recurrences: Some(RecurrenceExpansion::new(
+                TimeDelta::days(7),
+                TimeDelta::days(21),
+            )),
+            ..Default::default()
+        });
+        assert_eq!(detector.compare_at(calendar(), now).len(), 4);
+
+        // The 13th of January left the window and the 10th of February entered it
+        let later = now + TimeDelta::days(10);
+        assert!(detector.compare_at(calendar(), later).is_empty());
+        let mut tracked: Vec<&String> = detector.get_state().keys().collect();
+        tracked.sort();
+        assert_eq!(
+            tracked,
+            vec![
+                "lecture20250120T100000",
+                "lecture20250127T100000",
+                "lecture20250203T100000",
+                "lecture20250210T100000"
+            ]
+        );
+    }
+
+    #[test]
+    fn cancelled_occurrence_is_deleted() {
+        let start = chrono::Utc::now()
+            .date_naive()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        let cancelled = format_value(start + TimeDelta::days(7), ValueFormat::Local);
+        let calendar = |exdate: &str| {
+            testing::calendar(&format!(
+                "BEGIN:VEVENT\nUID:lecture\nDTSTART:{}\nRRULE:FREQ=WEEKLY;COUNT=3\n{exdate}END:VEVENT\n",
+                format_value(start, ValueFormat::Local)
+            ))
+        };
+
+        let mut detector = crate::CalendarChangeDetector::with_config(crate::DetectorConfig {
+            expand_recurrences: Some(RecurrenceExpansion::default()),
+            ..Default::default()
+        });
+        assert_eq!(detector.compare(calendar("")).len(), 3);
+
+        let changes = detector.compare(calendar(&format!("EXDATE:{cancelled}\n")));
+        match &changes[..] {
+            [crate::CalendarEvent::Deleted(data)] => {
+                assert_eq!(data.uid, format!("lecture{cancelled}"))
+            }
+            changes => panic!("Unexpected changes {changes:?}"),
+        }
+    }
+}
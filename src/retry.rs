@@ -0,0 +1,138 @@
+//! Retrying failed fetches with exponential backoff.
+//!
+//! See [RetryPolicy] and [ICSWatcher::set_retry_policy](crate::ICSWatcher::set_retry_policy).
+
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+use crate::Error;
+
+/// How often and how fast an [ICSWatcher](crate::ICSWatcher) retries a failed update.
+///
+/// Only transient errors (see [Error::is_transient]) are retried. Once all attempts are used up,
+/// or the error is permanent, the watcher waits for the next regular refresh and tries again.
+///
+/// The n-th retry waits `initial_backoff * multiplier^(n - 1)`, capped at `max_backoff`,
+/// randomly shortened or lengthened by up to `jitter` (a fraction of the backoff).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts per refresh, including the first one
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(5 * 60),
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retries, failed updates are only repeated on the next regular refresh
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// The time to wait before retrying after the `attempt`-th failed attempt
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let capped = backoff.min(self.max_backoff.as_secs_f64());
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 + jitter * (fastrand::f64() * 2.0 - 1.0);
+
+        // A huge max_backoff can exceed what a Duration can hold
+        Duration::try_from_secs_f64((capped * factor).max(0.0)).unwrap_or(self.max_backoff)
+    }
+}
+
+/// The state of the retries when an update failed, passed to the [ErrorHook]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryStatus {
+    /// Number of the failed attempt, starting at 1
+    pub attempt: u32,
+    /// Whether the error is expected to go away by itself, see [Error::is_transient]
+    pub transient: bool,
+    /// When the update will be retried, [None] if the watcher waits for the next regular refresh
+    pub retry_in: Option<Duration>,
+}
+
+/// Called with every error an [ICSWatcher](crate::ICSWatcher) encounters while updating
+pub type ErrorHook = Box<dyn Fn(&Error, &RetryStatus)>;
+
+/// Whether a server answering with `status` might succeed when asked again
+pub fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_EARLY
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_up_to_cap() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.0,
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(4), Duration::from_secs(8));
+        assert_eq!(policy.backoff(5), Duration::from_secs(10));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn backoff_beyond_duration_max() {
+        let policy = RetryPolicy {
+            max_backoff: Duration::MAX,
+            multiplier: 10.0,
+            jitter: 0.0,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.backoff(u32::MAX), Duration::MAX);
+    }
+
+    #[test]
+    fn backoff_jitter_stays_in_bounds() {
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            let backoff = policy.backoff(1);
+            assert!(backoff >= Duration::from_secs(1) && backoff <= Duration::from_secs(3));
+        }
+    }
+
+    #[test]
+    fn transient_statuses() {
+        assert!(is_transient_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_transient_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_transient_status(StatusCode::NOT_FOUND));
+        assert!(!is_transient_status(StatusCode::FORBIDDEN));
+    }
+}
@@ -0,0 +1,225 @@
+//! Watching several calendars from a single runtime.
+//!
+//! See [ICSSupervisor] to get started.
+
+use std::{future::Future, pin::Pin, time::Duration};
+
+use futures::future::join_all;
+use tracing::error;
+
+use crate::{CalendarCallback, CalendarEvent, CalendarSource, Error, ICSWatcher};
+
+/// Like a [CalendarCallback], but additionally receives the id of the feed the events came from
+pub type FeedCallback = Box<
+    dyn Fn(
+        String,
+        Option<String>,
+        Option<String>,
+        Vec<CalendarEvent>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send>>,
+>;
+
+struct Feed<'a> {
+    id: String,
+    watcher: ICSWatcher<'a>,
+    backup: Option<String>,
+}
+
+/// Watches multiple calendars (feeds) concurrently.
+///
+/// Every feed is an [ICSWatcher] of its own, with its own change detection, TTL, backup and callbacks.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use ics_watcher::{HttpSource, ICSSupervisor};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut supervisor = ICSSupervisor::new();
+/// supervisor
+///     .watch(
+///         "lectures",
+///         HttpSource::new("some url"),
+///         vec![Box::new(|feed, _, _, events| {
+///             Box::pin(async move {
+///                 println!("{feed}: {} changes", events.len());
+///                 Ok(())
+///             })
+///         })],
+///         Some("Lectures"),
+///     )
+///     .watch("exams", HttpSource::new("some other url"), vec![], Some("Exams"));
+///
+/// supervisor.load_backups();
+/// for (feed, error) in supervisor.run().await {
+///     eprintln!("Feed {feed} crashed: {error}");
+/// }
+/// # }
+/// ```
+#[derive(Default)]
+pub struct ICSSupervisor<'a> {
+    feeds: Vec<Feed<'a>>,
+}
+
+impl<'a> ICSSupervisor<'a> {
+    pub fn new() -> Self {
+        ICSSupervisor { feeds: Vec::new() }
+    }
+
+    /// Adds an already configured [ICSWatcher] as feed `id`, saving its backups as `backup`
+    pub fn add_feed(
+        &mut self,
+        id: impl Into<String>,
+        watcher: ICSWatcher<'a>,
+        backup: Option<&str>,
+    ) -> &mut Self {
+        self.feeds.push(Feed {
+            id: id.into(),
+            watcher,
+            backup: backup.map(String::from),
+        });
+        self
+    }
+
+    /// Watches `source` as feed `id`, passing the id of the feed to every callback
+    pub fn watch(
+        &mut self,
+        id: impl Into<String>,
+        source: impl CalendarSource + 'a,
+        callbacks: Vec<FeedCallback>,
+        backup: Option<&str>,
+    ) -> &mut Self {
+        let id = id.into();
+        let callbacks = callbacks
+            .into_iter()
+            .map(|callback| {
+                let id = id.clone();
+                Box::new(move |name, description, events| {
+                    callback(id.clone(), name, description, events)
+                }) as CalendarCallback
+            })
+            .collect();
+
+        self.add_feed(id, ICSWatcher::from_source(source, callbacks), backup)
+    }
+
+    /// Overrides the refresh interval of feed `id`, see [ICSWatcher::set_ttl]
+    pub fn set_ttl(&mut self, id: &str, ttl: Option<Duration>) -> &mut Self {
+        if let Some(watcher) = self.feed_mut(id) {
+            watcher.set_ttl(ttl);
+        }
+        self
+    }
+
+    pub fn feed(&self, id: &str) -> Option<&ICSWatcher<'a>> {
+        self.feeds
+            .iter()
+            .find(|feed| feed.id == id)
+            .map(|feed| &feed.watcher)
+    }
+
+    pub fn feed_mut(&mut self, id: &str) -> Option<&mut ICSWatcher<'a>> {
+        self.feeds
+            .iter_mut()
+            .find(|feed| feed.id == id)
+            .map(|feed| &mut feed.watcher)
+    }
+
+    /// Tries to load the backups of all feeds, feeds without a backup start from scratch
+    pub fn load_backups(&mut self) {
+        for feed in &mut self.feeds {
+            if let Some(backup) = &feed.backup {
+                let _ = feed.watcher.load_backup(backup);
+            }
+        }
+    }
+
+    /// Updates all feeds once, returning the feeds which failed to update
+    pub async fn update(&mut self) -> Vec<(String, Error)> {
+        join_all(
+            self.feeds
+                .iter_mut()
+                .map(|feed| async move { (feed.id.clone(), feed.watcher.update().await) }),
+        )
+        .await
+        .into_iter()
+        .filter_map(|(id, result)| result.err().map(|error| (id, error)))
+        .collect()
+    }
+
+    /// Runs all feeds concurrently until every one of them stopped, returning why they stopped.
+    ///
+    /// A crashing feed doesn't stop the other feeds.
+    pub async fn run(&mut self) -> Vec<(String, Error)> {
+        join_all(self.feeds.iter_mut().map(|feed| async move {
+            let result = feed.watcher.run(feed.backup.as_deref()).await;
+            if let Err(error) = &result {
+                error!(feed = %feed.id, %error, "Feed stopped");
+            }
+            (feed.id.clone(), result)
+        }))
+        .await
+        .into_iter()
+        .filter_map(|(id, result)| result.err().map(|error| (id, error)))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::source::StaticSource;
+
+    fn calendar(uid: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:{uid}\nSUMMARY:Lecture\nEND:VEVENT\nEND:VCALENDAR\n"
+        )
+    }
+
+    #[tokio::test]
+    async fn callbacks_know_their_feed() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let callback = |received: Arc<Mutex<Vec<(String, String)>>>| -> FeedCallback {
+            Box::new(move |feed, _, _, events| {
+                let received = received.clone();
+                Box::pin(async move {
+                    for event in events {
+                        if let CalendarEvent::Setup(data) = event {
+                            received.lock().unwrap().push((feed.clone(), data.uid));
+                        }
+                    }
+                    Ok(())
+                })
+            })
+        };
+
+        let mut supervisor = ICSSupervisor::new();
+        supervisor
+            .watch(
+                "first",
+                StaticSource::new(calendar("a")),
+                vec![callback(received.clone())],
+                None,
+            )
+            .watch(
+                "second",
+                StaticSource::new(calendar("b")),
+                vec![callback(received.clone())],
+                None,
+            );
+
+        assert!(supervisor.update().await.is_empty());
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(
+            received,
+            vec![
+                (String::from("first"), String::from("a")),
+                (String::from("second"), String::from("b"))
+            ]
+        );
+    }
+}
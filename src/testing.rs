@@ -0,0 +1,82 @@
+//! Fixtures shared by the tests of several modules
+
+use std::sync::{Arc, Mutex};
+
+use ical::{
+    parser::ical::component::{IcalCalendar, IcalEvent},
+    IcalParser,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    task::JoinHandle,
+};
+
+use crate::{CalendarCallback, CalendarEvent};
+
+/// Answer of [serve] telling the client that its version is still current
+pub(crate) const NOT_MODIFIED: &str =
+    "HTTP/1.1 304 Not Modified\r\nconnection: close\r\ncontent-length: 0\r\n\r\n";
+
+/// Parses a calendar with `contents` (properties and components) between its BEGIN and END
+pub(crate) fn calendar(contents: &str) -> IcalCalendar {
+    let ics = format!("BEGIN:VCALENDAR\n{contents}END:VCALENDAR\n");
+    IcalParser::new(ics.as_bytes()).next().unwrap().unwrap()
+}
+
+/// Parses an event with `properties`
+pub(crate) fn event(properties: &str) -> IcalEvent {
+    calendar(&format!("BEGIN:VEVENT\n{properties}END:VEVENT\n"))
+        .events
+        .remove(0)
+}
+
+/// Answer of [serve] with `body`, along with its `etag`
+pub(crate) fn ok(etag: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\netag: \"{etag}\"\r\nconnection: close\r\ncontent-length: {}\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Serves `requests` HTTP requests on a local port, answering each with `respond`, which gets the
+/// index and the lowercase text of the request. Returns the URL of the server.
+pub(crate) async fn serve(
+    requests: usize,
+    mut respond: impl FnMut(usize, &str) -> String + Send + 'static,
+) -> (String, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/calendar.ics", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        for index in 0..requests {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let read = stream.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..read]).to_lowercase();
+
+            let response = respond(index, &request);
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    (url, server)
+}
+
+/// The name of the calendar and the changes of every call of a [recorder]
+pub(crate) type Received = Arc<Mutex<Vec<(Option<String>, Vec<CalendarEvent>)>>>;
+
+/// A callback recording what it was called with
+pub(crate) fn recorder() -> (Received, CalendarCallback) {
+    let received = Received::default();
+    let callback: CalendarCallback = Box::new({
+        let received = received.clone();
+        move |name, _, events| {
+            let received = received.clone();
+            Box::pin(async move {
+                received.lock().unwrap().push((name, events));
+                Ok(())
+            })
+        }
+    });
+    (received, callback)
+}